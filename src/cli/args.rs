@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+/// Command line split into positional arguments and `--option value` pairs.
+pub struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    /// Options given more than once, under the same name or an alias, reported by
+    /// [`finish`](Args::finish).
+    repeated: Vec<String>,
+}
+
+impl Args {
+    /// `flags` are options that never take a value, every other option takes the next argument
+    /// as its value, even if it starts with `-` like negative numbers, or the one after `=`.
+    pub fn parse(args: impl Iterator<Item = String>, flags: &[&str]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut repeated = Vec::new();
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name.to_string(),
+                None => match arg.strip_prefix('-') {
+                    Some(short) if !short.is_empty() => short.to_string(),
+                    _ => {
+                        positional.push(arg);
+                        continue;
+                    },
+                },
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None if flags.contains(&name.as_str()) => (name, String::new()),
+                None => match args.next() {
+                    Some(value) => (name, value),
+                    None => return Err(format!("option `{arg}` needs a value")),
+                },
+            };
+            if options.contains_key(&name) {
+                repeated.push(name.clone());
+            }
+            options.insert(name, value);
+        }
+        Ok(Args { positional, options, repeated })
+    }
+
+    pub fn positional(&mut self, what: &str) -> Result<String, String> {
+        if self.positional.is_empty() {
+            return Err(format!("missing {what}"));
+        }
+        Ok(self.positional.remove(0))
+    }
+
+    /// The value of the first of `names` that was given, they are alternative spellings of the
+    /// same option.
+    pub fn option(&mut self, names: &[&str]) -> Option<String> {
+        let mut given = names.iter().filter_map(|name| self.options.remove(*name));
+        let result = given.next();
+        if given.next().is_some() {
+            self.repeated.push(names[0].to_string());
+        }
+        result
+    }
+
+    pub fn parsed_option<T: std::str::FromStr>(&mut self, names: &[&str]) -> Result<Option<T>, String> {
+        match self.option(names) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value `{value}` for --{}", names[0])),
+            None => Ok(None),
+        }
+    }
+
+    pub fn flag(&mut self, name: &str) -> bool {
+        self.options.remove(name).is_some()
+    }
+
+    /// Fails on anything that was not picked up by the command, or given more than once.
+    pub fn finish(self) -> Result<(), String> {
+        if let Some(option) = self.repeated.first() {
+            return Err(format!("option `--{option}` is given more than once"));
+        }
+        if let Some(arg) = self.positional.first() {
+            return Err(format!("unexpected argument `{arg}`"));
+        }
+        if let Some(option) = self.options.keys().next() {
+            return Err(format!("unknown option `--{option}`"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|it| it.to_string()), &["sprite", "flatten"])
+    }
+
+    #[test]
+    fn positional_arguments_and_options() {
+        let mut args = parse(&["build", "--format", "svg", "file.glyph", "-o", "out", "--sprite"]).unwrap();
+        assert_eq!(args.positional("command"), Ok(String::from("build")));
+        assert_eq!(args.option(&["output", "o"]), Some(String::from("out")));
+        assert_eq!(args.option(&["format", "f"]), Some(String::from("svg")));
+        assert!(args.flag("sprite"));
+        assert!(!args.flag("flatten"));
+        assert_eq!(args.positional("file"), Ok(String::from("file.glyph")));
+        assert_eq!(args.positional("name"), Err(String::from("missing name")));
+        assert!(args.finish().is_ok());
+    }
+
+    #[test]
+    fn values_may_start_with_a_dash() {
+        let mut args = parse(&["render", "--margin", "-0.1", "--baseline=-1", "--name", "-", "--family", "--sprite"]).unwrap();
+        assert_eq!(args.parsed_option::<f32>(&["margin"]), Ok(Some(-0.1)));
+        assert_eq!(args.parsed_option::<f32>(&["baseline"]), Ok(Some(-1.0)));
+        assert_eq!(args.option(&["name"]), Some(String::from("-")));
+        // only flags are never followed by a value
+        assert_eq!(args.option(&["family"]), Some(String::from("--sprite")));
+        assert!(!args.flag("sprite"));
+    }
+
+    #[test]
+    fn missing_values_are_reported() {
+        assert_eq!(parse(&["build", "file.glyph", "--format"]).err(), Some(String::from("option `--format` needs a value")));
+        assert_eq!(parse(&["render", "-n"]).err(), Some(String::from("option `-n` needs a value")));
+        let mut args = parse(&["build", "--format="]).unwrap();
+        assert_eq!(args.option(&["format"]), Some(String::new()));
+    }
+
+    #[test]
+    fn invalid_values_name_the_option() {
+        let mut args = parse(&["--size", "big", "-c", "3"]).unwrap();
+        assert_eq!(args.parsed_option::<usize>(&["size"]), Err(String::from("invalid value `big` for --size")));
+        assert_eq!(args.parsed_option::<usize>(&["columns", "c"]), Ok(Some(3)));
+    }
+
+    #[test]
+    fn leftovers_and_repeats_fail() {
+        let mut args = parse(&["check", "file.glyph", "extra"]).unwrap();
+        args.positional("command").unwrap();
+        args.positional("file").unwrap();
+        assert_eq!(args.finish(), Err(String::from("unexpected argument `extra`")));
+
+        let args = parse(&["--bogus", "1"]).unwrap();
+        assert_eq!(args.finish(), Err(String::from("unknown option `--bogus`")));
+
+        let args = parse(&["build", "--format", "svg", "--format", "json"]).unwrap();
+        assert_eq!(args.finish(), Err(String::from("option `--format` is given more than once")));
+
+        let mut args = parse(&["build", "--output", "a", "-o", "b"]).unwrap();
+        assert_eq!(args.option(&["output", "o"]), Some(String::from("a")));
+        assert_eq!(args.finish(), Err(String::from("option `--output` is given more than once")));
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt::Write;
+use vglyph::Segment;
+
+pub fn string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(result, "\\u{:04x}", c as u32).unwrap(),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+pub fn strings(values: &[String]) -> String {
+    let values: Vec<_> = values.iter().map(|it| string(it)).collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// Every segment as its list of points, two for lines and four for cubic curves.
+pub fn segments(segments: &[Segment]) -> String {
+    let segments: Vec<_> = segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Line(from, to) => points(&[*from, *to]),
+            Segment::Cubic(p0, p1, p2, p3) => points(&[*p0, *p1, *p2, *p3]),
+        })
+        .collect();
+    format!("[{}]", segments.join(", "))
+}
+
+fn points(points: &[(f32, f32)]) -> String {
+    let points: Vec<_> = points.iter().map(|(x, y)| format!("[{x}, {y}]")).collect();
+    format!("[{}]", points.join(", "))
+}
@@ -0,0 +1,528 @@
+mod args;
+mod json;
+mod watch;
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use args::Args;
+use vglyph::{compile_glyphs, Diagnostic, Glyph, GlyphSet, Path as GlyphPath, Segment};
+use vglyph::export::{self, FileNames, FontError, FontGlyph, FontOptions, PngOptions, SpecimenOptions, SvgOptions};
+use vglyph::outline::Pen;
+
+const FLAGS: &[&str] = &["sprite", "bounds", "flatten"];
+
+const USAGE: &str = "\
+usage: vglyph <command> [options]
+
+commands:
+    check <file>                       report diagnostics only
+    build <file> [--format json|text|svg|ttf] [-o <path>]
+                                       compile every character, svg writes one file per
+                                       character into the directory given by -o
+    list <file>                        list all characters
+    show <file> <name>                 print a single character, looked up by name or alias
+    render <file> --name <name> [-o <path>]
+                                       rasterise a single character into a png
+    specimen <file> [--format svg|png] [-o <path>]
+                                       lay out every character in a grid, with its names and
+                                       input sequence, png if the output ends in .png
+    site <file> -o <directory>         write an html page per character, with its rendering,
+                                       decomposition and the characters using it
+    watch <file> -o <directory> [--format png|svg] [--interval <ms>]
+                                       re-render changed characters whenever the file is
+                                       saved, characters with errors keep their last image,
+                                       polling every 500 ms or the given interval (at least 50)
+
+stroke options (svg, ttf, render, specimen, site, watch):
+    --stroke-width <em>                stroke width relative to the glyph size (default 0.05)
+    --line-cap butt|round|square       (default round)
+    --line-join miter|round|bevel      (default round)
+    --miter-limit <ratio>              longer miter joins are beveled (default 4)
+
+image options (svg, render, specimen, site, watch):
+    --size <px>                        canvas size, not for specimen (default 256)
+    --margin <em>                      space around the glyph square (default 0, specimen 0.15)
+
+svg options (svg, site, watch):
+    --sprite                           write a single sprite sheet with one <symbol> per character
+    --grid <cells>                     draw a helper grid
+    --bounds                           outline the glyph square and the ink bounds
+
+colour options (render, specimen, watch with png):
+    --background <color>               #rgb, #rrggbb, #rrggbbaa or transparent (default #fff)
+    --foreground <color>               (default #000)
+
+specimen options:
+    --cell <px>                        size of each glyph in the grid (default 96)
+    --columns <count>                  (default 8)
+
+ttf options:
+    --family <name>                    font family name (default vglyph)
+    --first-codepoint <hex>            codepoint of the first character, in definition order (default E000)
+    --codepoints <file>                explicit codepoints, one `name U+XXXX` per line
+    --units-per-em <units>             16 to 16384 (default 1000)
+    --advance <em>                     advance width (default 1)
+    --baseline <em>                    baseline, measured from the top of the glyph (default 0.8)
+
+curve options:
+    --flatten                          write curves as straight lines (json, text, svg)
+    --tolerance <em>                   how far straight lines may stray from the curves they
+                                       replace, in ttf outlines and with --flatten (default 0.001)";
+
+enum Failure {
+    Usage(String),
+    Io(String),
+    Diagnostics,
+    /// No character has this name or alias.
+    UnknownCharacter(String),
+    Font(FontError),
+}
+
+pub fn run(args: impl Iterator<Item = String>) -> ExitCode {
+    let result = Args::parse(args, FLAGS).map_err(Failure::Usage).and_then(|mut args| {
+        let command = args.positional("command").map_err(Failure::Usage)?;
+        match command.as_str() {
+            "check" => check(args),
+            "build" => build(args),
+            "list" => list(args),
+            "show" => show(args),
+            "render" => render(args),
+            "specimen" => specimen(args),
+            "site" => site(args),
+            "watch" => watch::watch(args),
+            "help" => {
+                println!("{USAGE}");
+                Ok(())
+            },
+            _ => Err(Failure::Usage(format!("unknown command `{command}`"))),
+        }
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Diagnostics) => ExitCode::from(1),
+        Err(Failure::Usage(message)) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        },
+        Err(Failure::Io(message)) => {
+            eprintln!("error: {message}");
+            ExitCode::from(2)
+        },
+        Err(Failure::UnknownCharacter(name)) => {
+            eprintln!("error: no character named `{name}`");
+            ExitCode::from(2)
+        },
+        Err(Failure::Font(error)) => {
+            eprintln!("error: cannot build the font: {error}");
+            ExitCode::from(2)
+        },
+    }
+}
+
+fn load(args: &mut Args) -> Result<(String, GlyphSet), Failure> {
+    let (path, _, glyphs) = load_source(args)?;
+    Ok((path, glyphs))
+}
+
+/// Like [`load`], also returning the source.
+fn load_source(args: &mut Args) -> Result<(String, String, GlyphSet), Failure> {
+    let path = args.positional("file").map_err(Failure::Usage)?;
+    let src = fs::read_to_string(&path).map_err(|e| Failure::Io(format!("cannot read `{path}`: {e}")))?;
+    let glyphs = compile_glyphs(&src).map_err(|diagnostics| {
+        report(&path, &diagnostics);
+        Failure::Diagnostics
+    })?;
+    Ok((path, src, glyphs))
+}
+
+fn report(path: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{path}:{diagnostic}");
+    }
+}
+
+fn write_output(output: Option<String>, content: impl AsRef<[u8]>) -> Result<(), Failure> {
+    match output {
+        Some(path) => fs::write(&path, content).map_err(|e| Failure::Io(format!("cannot write `{path}`: {e}"))),
+        None => io::stdout()
+            .write_all(content.as_ref())
+            .map_err(|e| Failure::Io(format!("cannot write output: {e}"))),
+    }
+}
+
+/// The paths of `glyph`, with their curves replaced by straight lines if a tolerance is given.
+fn paths(glyph: &Glyph, flatten: Option<f32>) -> Cow<'_, [GlyphPath]> {
+    match flatten {
+        None => Cow::Borrowed(&glyph.paths),
+        Some(tolerance) => Cow::Owned(
+            glyph.paths
+                .iter()
+                .map(|path| GlyphPath {
+                    segments: path.flatten(tolerance).windows(2).map(|pair| Segment::Line(pair[0], pair[1])).collect(),
+                })
+                .collect(),
+        ),
+    }
+}
+
+fn check(mut args: Args) -> Result<(), Failure> {
+    let (path, glyphs) = load(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+    println!("{path}: {} characters ok", glyphs.len());
+    Ok(())
+}
+
+fn build(mut args: Args) -> Result<(), Failure> {
+    let format = args.option(&["format", "f"]).unwrap_or_else(|| String::from("json"));
+    let output = args.option(&["output", "o"]);
+    let pen = pen(&mut args).map_err(Failure::Usage)?;
+    let svg_options = svg_options(&mut args, &pen).map_err(Failure::Usage)?;
+    let font_options = font_options(&mut args, pen).map_err(Failure::Usage)?;
+    let first_codepoint = args.option(&["first-codepoint"]).unwrap_or_else(|| String::from("E000"));
+    let first_codepoint = u32::from_str_radix(first_codepoint.trim_start_matches("U+"), 16)
+        .ok()
+        .filter(|codepoint| char::from_u32(*codepoint).is_some())
+        .ok_or_else(|| Failure::Usage(format!("invalid codepoint `{first_codepoint}`")))?;
+    let codepoints = args.option(&["codepoints"]);
+    let sprite = args.flag("sprite");
+    let flatten = args.flag("flatten").then_some(font_options.tolerance);
+    let (_, glyphs) = load(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+
+    let content = match format.as_str() {
+        "json" => {
+            let entries: Vec<_> = glyphs
+                .iter()
+                .map(|glyph| {
+                    let segments: Vec<_> = paths(glyph, flatten).iter().flat_map(|path| path.segments.clone()).collect();
+                    format!(
+                        "  {}: {{\"names\": {}, \"input\": {}, \"segments\": {}}}",
+                        json::string(glyph.name()),
+                        json::strings(&glyph.names),
+                        json::strings(&glyph.input),
+                        json::segments(&segments),
+                    )
+                })
+                .collect();
+            format!("{{\n{}\n}}\n", entries.join(",\n"))
+        },
+        "text" => glyphs.iter().map(|glyph| describe(glyph, &paths(glyph, flatten))).collect(),
+        "svg" if sprite => {
+            let paths: Vec<_> = glyphs.iter().map(|glyph| (glyph.name(), paths(glyph, flatten))).collect();
+            export::sprite_sheet(paths.iter().map(|(name, paths)| (*name, paths.as_ref())), &svg_options)
+        },
+        "svg" => {
+            let directory = output.ok_or_else(|| Failure::Usage(String::from("svg output needs a directory, given by -o")))?;
+            let directory = Path::new(&directory);
+            fs::create_dir_all(directory).map_err(|e| Failure::Io(format!("cannot create `{}`: {e}", directory.display())))?;
+            let mut file_names = FileNames::new();
+            for glyph in &glyphs {
+                let path = directory.join(file_names.file_name(glyph.name(), "svg"));
+                fs::write(&path, export::glyph_svg(&paths(glyph, flatten), &svg_options))
+                    .map_err(|e| Failure::Io(format!("cannot write `{}`: {e}", path.display())))?;
+            }
+            return Ok(());
+        },
+        "ttf" => {
+            let codepoints = match codepoints {
+                Some(path) => read_codepoints(&path, &glyphs)?,
+                None => HashMap::new(),
+            };
+            let glyphs = assign_codepoints(&glyphs, &codepoints, first_codepoint)?;
+            let font = export::font(&glyphs, &font_options).map_err(Failure::Font)?;
+            return write_output(output, font);
+        },
+        _ => return Err(Failure::Usage(format!("unknown format `{format}`"))),
+    };
+    write_output(output, &content)
+}
+
+fn list(mut args: Args) -> Result<(), Failure> {
+    let (_, glyphs) = load(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+    for glyph in &glyphs {
+        println!("{}\t{}", glyph.names.join(", "), glyph.input.join(" "));
+    }
+    Ok(())
+}
+
+fn show(mut args: Args) -> Result<(), Failure> {
+    let (_, glyphs) = load(&mut args)?;
+    let name = args.positional("name").map_err(Failure::Usage)?;
+    args.finish().map_err(Failure::Usage)?;
+    let glyph = glyphs.get(&name).ok_or(Failure::UnknownCharacter(name))?;
+    print!("{}", describe(glyph, &glyph.paths));
+    Ok(())
+}
+
+fn render(mut args: Args) -> Result<(), Failure> {
+    let name = args.option(&["name", "n"]).ok_or_else(|| Failure::Usage(String::from("render needs a character, given by --name")))?;
+    let output = args.option(&["output", "o"]);
+    let pen = pen(&mut args).map_err(Failure::Usage)?;
+    let options = png_options(&mut args, pen).map_err(Failure::Usage)?;
+    let (_, glyphs) = load(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+    let glyph = glyphs.get(&name).ok_or(Failure::UnknownCharacter(name))?;
+    write_output(output, export::glyph_png(&glyph.paths, &options))
+}
+
+fn specimen(mut args: Args) -> Result<(), Failure> {
+    let output = args.option(&["output", "o"]);
+    let format = args.option(&["format", "f"]).unwrap_or_else(|| match &output {
+        Some(output) if output.ends_with(".png") => String::from("png"),
+        _ => String::from("svg"),
+    });
+    let pen = pen(&mut args).map_err(Failure::Usage)?;
+    let options = specimen_options(&mut args, pen).map_err(Failure::Usage)?;
+    let (_, glyphs) = load(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+    match format.as_str() {
+        "svg" => write_output(output, export::specimen_svg(&glyphs, &options)),
+        "png" => write_output(output, export::specimen_png(&glyphs, &options)),
+        _ => Err(Failure::Usage(format!("unknown format `{format}`"))),
+    }
+}
+
+fn site(mut args: Args) -> Result<(), Failure> {
+    let directory = args.option(&["output", "o"]).ok_or_else(|| Failure::Usage(String::from("site needs a directory, given by -o")))?;
+    let pen = pen(&mut args).map_err(Failure::Usage)?;
+    let options = svg_options(&mut args, &pen).map_err(Failure::Usage)?;
+    let (_, src, glyphs) = load_source(&mut args)?;
+    args.finish().map_err(Failure::Usage)?;
+
+    let directory = Path::new(&directory);
+    fs::create_dir_all(directory).map_err(|e| Failure::Io(format!("cannot create `{}`: {e}", directory.display())))?;
+    for (name, content) in export::site(&src, &glyphs, &options) {
+        let path = directory.join(name);
+        fs::write(&path, content).map_err(|e| Failure::Io(format!("cannot write `{}`: {e}", path.display())))?;
+    }
+    Ok(())
+}
+
+/// Paths are written the way they would be in a glyph file.
+fn describe(glyph: &Glyph, paths: &[GlyphPath]) -> String {
+    let mut result = format!("{}\n    input: {}\n", glyph.names.join(", "), glyph.input.join(" "));
+    for path in paths {
+        let Some((x, y)) = path.start() else {
+            continue;
+        };
+        result.push_str(&format!("    {x} {y}"));
+        for segment in &path.segments {
+            match segment {
+                Segment::Line(_, (x1, y1)) => result.push_str(&format!(" -> {x1} {y1}")),
+                Segment::Cubic(_, (x1, y1), (x2, y2), (x3, y3)) => {
+                    result.push_str(&format!(" ~> {x1} {y1} ~> {x2} {y2} -> {x3} {y3}"))
+                },
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
+
+fn pen(args: &mut Args) -> Result<Pen, String> {
+    let mut pen = Pen::default();
+    if let Some(width) = args.parsed_option::<f32>(&["stroke-width"])? {
+        // negative widths would put the joins on the inner side of every corner
+        if !(width > 0.0 && width.is_finite()) {
+            return Err(format!("invalid value `{width}` for --stroke-width, it must be above 0"));
+        }
+        pen.width = width;
+    }
+    if let Some(cap) = args.parsed_option(&["line-cap"])? {
+        pen.cap = cap;
+    }
+    if let Some(join) = args.parsed_option(&["line-join"])? {
+        pen.join = join;
+    }
+    if let Some(miter_limit) = args.parsed_option::<f32>(&["miter-limit"])? {
+        // as in SVG, no miter is shorter than the stroke is wide
+        if !(miter_limit >= 1.0 && miter_limit.is_finite()) {
+            return Err(format!("invalid value `{miter_limit}` for --miter-limit, it must be at least 1"));
+        }
+        pen.miter_limit = miter_limit;
+    }
+    Ok(pen)
+}
+
+fn svg_options(args: &mut Args, pen: &Pen) -> Result<SvgOptions, String> {
+    let mut options = SvgOptions {
+        stroke_width: pen.width,
+        line_cap: pen.cap,
+        line_join: pen.join,
+        miter_limit: pen.miter_limit,
+        ..SvgOptions::default()
+    };
+    if let Some(size) = args.parsed_option::<f32>(&["size"])? {
+        if !(size > 0.0 && size.is_finite()) {
+            return Err(format!("invalid value `{size}` for --size, it must be above 0"));
+        }
+        options.size = size;
+    }
+    if let Some(margin) = args.parsed_option(&["margin"])? {
+        options.margin = margin_option(margin)?;
+    }
+    options.grid = args.parsed_option(&["grid"])?;
+    options.bounds = args.flag("bounds");
+    Ok(options)
+}
+
+fn png_options(args: &mut Args, pen: Pen) -> Result<PngOptions, String> {
+    let mut options = PngOptions { pen, ..PngOptions::default() };
+    if let Some(size) = args.parsed_option(&["size"])? {
+        options.size = positive(size, "size")?;
+    }
+    if let Some(margin) = args.parsed_option(&["margin"])? {
+        options.margin = margin_option(margin)?;
+    }
+    if let Some(background) = args.parsed_option(&["background"])? {
+        options.background = background;
+    }
+    if let Some(foreground) = args.parsed_option(&["foreground"])? {
+        options.foreground = foreground;
+    }
+    Ok(options)
+}
+
+/// Rejects sizes of zero pixels, which make empty images.
+fn positive(value: usize, name: &str) -> Result<usize, String> {
+    match value {
+        0 => Err(format!("--{name} must be at least 1")),
+        _ => Ok(value),
+    }
+}
+
+/// Rejects margins that leave no room for the glyph square, they cover `margin` on both sides.
+fn margin_option(margin: f32) -> Result<f32, String> {
+    match 1.0 + 2.0 * margin > 0.0 && margin.is_finite() {
+        true => Ok(margin),
+        false => Err(format!("invalid value `{margin}` for --margin, it must be above -0.5")),
+    }
+}
+
+fn specimen_options(args: &mut Args, pen: Pen) -> Result<SpecimenOptions, String> {
+    let mut options = SpecimenOptions { pen, ..SpecimenOptions::default() };
+    if let Some(cell) = args.parsed_option(&["cell"])? {
+        options.cell = positive(cell, "cell")?;
+    }
+    if let Some(columns) = args.parsed_option(&["columns"])? {
+        options.columns = positive(columns, "columns")?;
+    }
+    if let Some(margin) = args.parsed_option(&["margin"])? {
+        options.margin = margin_option(margin)?;
+    }
+    if let Some(background) = args.parsed_option(&["background"])? {
+        options.background = background;
+    }
+    if let Some(foreground) = args.parsed_option(&["foreground"])? {
+        options.foreground = foreground;
+    }
+    Ok(options)
+}
+
+fn font_options(args: &mut Args, pen: Pen) -> Result<FontOptions, String> {
+    let mut options = FontOptions { pen, ..FontOptions::default() };
+    if let Some(family) = args.option(&["family"]) {
+        options.family = family;
+    }
+    if let Some(units_per_em) = args.parsed_option(&["units-per-em"])? {
+        if !(16..=16384).contains(&units_per_em) {
+            return Err(format!("invalid value `{units_per_em}` for --units-per-em, it must be between 16 and 16384"));
+        }
+        options.units_per_em = units_per_em;
+    }
+    if let Some(advance) = args.parsed_option(&["advance"])? {
+        options.advance = advance;
+    }
+    if let Some(baseline) = args.parsed_option(&["baseline"])? {
+        options.baseline = baseline;
+    }
+    if let Some(tolerance) = args.parsed_option::<f32>(&["tolerance"])? {
+        // zero would split every curve as often as flattening allows
+        if !(tolerance > 0.0 && tolerance.is_finite()) {
+            return Err(format!("invalid value `{tolerance}` for --tolerance, it must be above 0"));
+        }
+        options.tolerance = tolerance;
+    }
+    Ok(options)
+}
+
+/// Reads `name codepoint` lines, where the codepoint is written as `U+E000`, `0xE000` or the
+/// character itself. The result is keyed by the first name of each character, which may be
+/// given by any of its names, but only once. Names of no character and codepoints given twice
+/// are errors, since one of them would go missing from the font.
+fn read_codepoints(path: &str, glyphs: &GlyphSet) -> Result<HashMap<String, u32>, Failure> {
+    let content = fs::read_to_string(path).map_err(|e| Failure::Io(format!("cannot read `{path}`: {e}")))?;
+    let mut result = HashMap::new();
+    // the line each character and each codepoint was given on
+    let mut lines: HashMap<&str, usize> = HashMap::new();
+    let mut codepoint_lines: HashMap<u32, usize> = HashMap::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = idx + 1;
+        let error = |message: String| Failure::Io(format!("{path}:{number}: {message}"));
+        let invalid = || error(String::from("expected `name codepoint`"));
+        let (name, codepoint) = line.rsplit_once(char::is_whitespace).ok_or_else(invalid)?;
+        let codepoint = match codepoint.strip_prefix("U+").or_else(|| codepoint.strip_prefix("0x")) {
+            Some(hex) => {
+                let value = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+                // surrogates and anything past U+10FFFF can't be mapped in a font
+                if char::from_u32(value).is_none() {
+                    return Err(error(format!("`{codepoint}` is not a valid codepoint")));
+                }
+                value
+            },
+            None => {
+                let mut chars = codepoint.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c as u32,
+                    _ => return Err(invalid()),
+                }
+            },
+        };
+
+        let name = name.trim();
+        let glyph = glyphs.get(name).ok_or_else(|| error(format!("no character named `{name}`")))?;
+        if let Some(previous) = lines.insert(glyph.name(), number) {
+            return Err(error(format!("`{name}` already has a codepoint, given on line {previous}")));
+        }
+        if let Some(previous) = codepoint_lines.insert(codepoint, number) {
+            return Err(error(format!("U+{codepoint:04X} is already taken on line {previous}")));
+        }
+        result.insert(glyph.name().to_string(), codepoint);
+    }
+    Ok(result)
+}
+
+/// Explicit codepoints (keyed by first name) win, the other characters are numbered from
+/// `first` in definition order, skipping codepoints that are already taken and surrogates.
+fn assign_codepoints<'a>(glyphs: &'a GlyphSet, explicit: &HashMap<String, u32>, first: u32) -> Result<Vec<FontGlyph<'a>>, Failure> {
+    let mut taken: HashSet<u32> = explicit.values().copied().collect();
+    let mut next = first;
+    let mut result = Vec::new();
+    for glyph in glyphs {
+        let codepoint = match explicit.get(glyph.name()) {
+            Some(codepoint) => *codepoint,
+            None => {
+                while taken.contains(&next) || (0xD800..=0xDFFF).contains(&next) {
+                    next += 1;
+                }
+                if char::from_u32(next).is_none() {
+                    return Err(Failure::Usage(String::from("ran out of codepoints, --first-codepoint is too high")));
+                }
+                taken.insert(next);
+                next
+            },
+        };
+        result.push(FontGlyph { codepoint, paths: &glyph.paths });
+    }
+    Ok(result)
+}
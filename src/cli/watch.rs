@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use vglyph::export::{self, FileNames, PngOptions, SvgOptions};
+use vglyph::{compile_partial, Path as GlyphPath};
+use super::args::Args;
+use super::{pen, png_options, report, svg_options, Failure};
+
+/// Shortest time between two polls, in milliseconds.
+const MIN_INTERVAL: u64 = 50;
+
+enum Format {
+    Png(PngOptions),
+    Svg(SvgOptions),
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Png(_) => "png",
+            Format::Svg(_) => "svg",
+        }
+    }
+}
+
+/// What has been written so far, kept across rebuilds.
+#[derive(Default)]
+struct Output {
+    /// File name by first name. A character keeps its file for as long as the watch runs, even
+    /// when it fails or disappears, so that no other character takes it over.
+    files: HashMap<String, String>,
+    file_names: FileNames,
+    /// The paths last written for each first name.
+    rendered: HashMap<String, Vec<GlyphPath>>,
+}
+
+/// Polls `file` for changes and renders every character whose paths changed since it was last
+/// rendered into one file per character. Characters that fail keep their previous file. Runs
+/// until interrupted.
+pub(super) fn watch(mut args: Args) -> Result<(), Failure> {
+    let directory = args.option(&["output", "o"]).ok_or_else(|| Failure::Usage(String::from("watch needs a directory, given by -o")))?;
+    let format = args.option(&["format", "f"]).unwrap_or_else(|| String::from("png"));
+    let interval: u64 = args.parsed_option(&["interval"]).map_err(Failure::Usage)?.unwrap_or(500);
+    // anything shorter keeps a core busy with polling
+    if interval < MIN_INTERVAL {
+        return Err(Failure::Usage(format!("invalid value `{interval}` for --interval, it must be at least {MIN_INTERVAL}")));
+    }
+    let pen = pen(&mut args).map_err(Failure::Usage)?;
+    let format = match format.as_str() {
+        "png" => Format::Png(png_options(&mut args, pen).map_err(Failure::Usage)?),
+        "svg" => Format::Svg(svg_options(&mut args, &pen).map_err(Failure::Usage)?),
+        _ => return Err(Failure::Usage(format!("unknown format `{format}`"))),
+    };
+    let path = args.positional("file").map_err(Failure::Usage)?;
+    args.finish().map_err(Failure::Usage)?;
+
+    let directory = Path::new(&directory);
+    fs::create_dir_all(directory).map_err(|e| Failure::Io(format!("cannot create `{}`: {e}", directory.display())))?;
+    modified(&path).map_err(|e| Failure::Io(format!("cannot read `{path}`: {e}")))?;
+    println!("watching {path}, press Ctrl-C to stop");
+
+    let mut output = Output::default();
+    let mut last_seen = None;
+    loop {
+        // the file may briefly be missing or half written while an editor saves it, so errors
+        // are reported and the next poll tries again
+        match modified(&path) {
+            Ok(stamp) if Some(stamp) != last_seen => match fs::read_to_string(&path) {
+                Ok(src) => {
+                    last_seen = Some(stamp);
+                    rebuild(&path, &src, directory, &format, &mut output);
+                },
+                Err(e) => eprintln!("error: cannot read `{path}`: {e}"),
+            },
+            Ok(_) => {},
+            Err(e) => eprintln!("error: cannot read `{path}`: {e}"),
+        }
+        thread::sleep(Duration::from_millis(interval));
+    }
+}
+
+/// Modification time and length, either changes when the file is saved.
+fn modified(path: &str) -> std::io::Result<(SystemTime, u64)> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.modified()?, metadata.len()))
+}
+
+/// Renders the characters of `src` that changed into `directory`, returning how many.
+fn rebuild(path: &str, src: &str, directory: &Path, format: &Format, output: &mut Output) -> usize {
+    let (glyphs, diagnostics) = compile_partial(src);
+    report(path, &diagnostics);
+
+    let mut count = 0;
+    for glyph in &glyphs {
+        if output.rendered.get(glyph.name()) == Some(&glyph.paths) {
+            continue;
+        }
+        let content = match format {
+            Format::Png(options) => export::glyph_png(&glyph.paths, options),
+            Format::Svg(options) => export::glyph_svg(&glyph.paths, options).into_bytes(),
+        };
+        let file_name = output
+            .files
+            .entry(glyph.name().to_string())
+            .or_insert_with(|| output.file_names.file_name(glyph.name(), format.extension()));
+        let file = directory.join(file_name);
+        match fs::write(&file, content) {
+            Ok(()) => {
+                output.rendered.insert(glyph.name().to_string(), glyph.paths.clone());
+                count += 1;
+            },
+            Err(e) => eprintln!("error: cannot write `{}`: {e}", file.display()),
+        }
+    }
+    println!("{path}: {} characters compiled, {count} rendered, {} problems", glyphs.len(), diagnostics.len());
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test.
+    fn directory(name: &str) -> std::path::PathBuf {
+        let result = std::env::temp_dir().join(format!("vglyph-watch-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&result);
+        fs::create_dir_all(&result).unwrap();
+        result
+    }
+
+    fn read(directory: &Path, file: &str) -> String {
+        fs::read_to_string(directory.join(file)).unwrap()
+    }
+
+    #[test]
+    fn only_changed_characters_are_rendered() {
+        let directory = directory("changed");
+        let format = Format::Svg(SvgOptions::default());
+        let mut output = Output::default();
+        assert_eq!(rebuild("a.glyph", "a := 0 0 -> 1 1;\nb := a | a;", &directory, &format, &mut output), 2);
+        assert!(read(&directory, "b.svg").contains("M0 0L0.5 1M0.5 0L1 1"));
+
+        // a marker to see whether `b` is written again
+        fs::write(directory.join("b.svg"), "untouched").unwrap();
+        assert_eq!(rebuild("a.glyph", "a := 0 0 -> 1 1;\nb := a | a;\nc := a;", &directory, &format, &mut output), 1);
+        assert_eq!(read(&directory, "b.svg"), "untouched");
+
+        // a change to a component re-renders everything using it
+        assert_eq!(rebuild("a.glyph", "a := 0 1 -> 1 0;\nb := a | a;\nc := 0 0 -> 1 1;", &directory, &format, &mut output), 2);
+        assert!(read(&directory, "a.svg").contains("M0 1L1 0"));
+        assert!(read(&directory, "b.svg").contains("M0 1L0.5 0"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn failing_characters_keep_their_last_output() {
+        let directory = directory("failing");
+        let format = Format::Svg(SvgOptions::default());
+        let mut output = Output::default();
+        rebuild("a.glyph", "a := 0 0 -> 1 1;\nb := 0 0 -> 1 0;", &directory, &format, &mut output);
+        let before = read(&directory, "b.svg");
+
+        assert_eq!(rebuild("a.glyph", "a := 0 1 -> 1 1;\nb := 0 0 -> ;", &directory, &format, &mut output), 1);
+        assert_eq!(read(&directory, "b.svg"), before);
+        assert!(read(&directory, "a.svg").contains("M0 1L1 1"));
+
+        // fixed as it was, the file is still up to date, fixed differently, it is replaced
+        assert_eq!(rebuild("a.glyph", "a := 0 1 -> 1 1;\nb := 0 0 -> 1 0;", &directory, &format, &mut output), 0);
+        assert_eq!(rebuild("a.glyph", "a := 0 1 -> 1 1;\nb := 0 0 -> 0 1;", &directory, &format, &mut output), 1);
+        assert!(read(&directory, "b.svg").contains("M0 0L0 1"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn file_names_stay_with_their_character() {
+        let directory = directory("names");
+        let format = Format::Svg(SvgOptions::default());
+        let mut output = Output::default();
+        rebuild("a.glyph", "a := 0 0 -> 1 1;\n\"a b\" := 0 0 -> 1 0;", &directory, &format, &mut output);
+        assert_eq!(output.files["a b"], "a_b.svg");
+
+        // `a` is gone for now, but `A` still doesn't take over its file
+        rebuild("a.glyph", "A := 0 1 -> 1 0;\na_b := 0 0 -> 0 1;\n\"a b\" := 0 0 -> 1 0;", &directory, &format, &mut output);
+        assert_eq!((output.files["A"].as_str(), output.files["a_b"].as_str()), ("A-2.svg", "a_b-2.svg"));
+        assert_eq!(output.files["a b"], "a_b.svg");
+        assert!(read(&directory, "a.svg").contains("M0 0L1 1"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn saving_changes_the_stamp() {
+        let directory = directory("stamp");
+        let file = directory.join("a.glyph");
+        let path = file.to_str().unwrap();
+        fs::write(&file, "a := 0 0 -> 1 1;").unwrap();
+        let before = modified(path).unwrap();
+        assert_eq!(modified(path).unwrap(), before);
+        fs::write(&file, "a := 0 0 -> 1 1 + 0 1 -> 1 0;").unwrap();
+        assert_ne!(modified(path).unwrap(), before);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(modified(path).is_err());
+    }
+}
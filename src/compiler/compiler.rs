@@ -1,61 +1,52 @@
 use std::collections::HashMap;
-use crate::compiler::{compile_smooth_line, Line};
+use crate::compiler::{lookup, Path, Segment};
+use crate::diagnostic::Diagnostic;
 use crate::parser::character::Annotations;
 use crate::parser::SyntaxTree;
 
+type Bounds = (f32, f32, f32, f32);
+
 impl SyntaxTree {
     fn weight(&self) -> f32 {
         match self {
-            SyntaxTree::Shrink(shrinkage, _) => 1.0 / (*shrinkage + 1) as f32,
+            SyntaxTree::Shrink(shrinkage, _, _) => 1.0 / (*shrinkage + 1) as f32,
             _ => 1.0,
         }
     }
 
-    pub fn compile(&self, reference: &HashMap<String, (Vec<Line>, Annotations)>, bounds: (f32, f32, f32, f32)) -> Vec<Line> {
+    /// Every `->` chain becomes one path, so its segments meet at joins rather than ending in caps.
+    pub(crate) fn compile(&self, reference: &HashMap<String, (Vec<Path>, Annotations)>, bounds: (f32, f32, f32, f32)) -> Result<Vec<Path>, Diagnostic> {
         match self {
-            SyntaxTree::Lines(lines) => {
-                let lines = lines
+            SyntaxTree::Lines(lines, _) => {
+                let segments: Vec<Segment> = lines
                     .iter()
-                    .flat_map(|line| compile_smooth_line(line))
+                    .flat_map(|line| Segment::from_control_points(line))
                     .collect();
-                fit_inside(lines, bounds)
+                if segments.is_empty() {
+                    return Ok(Vec::new());
+                }
+                Ok(fit_inside(vec![Path { segments }], bounds))
             },
-            SyntaxTree::Bounds(_, _) => {
-                Vec::new()
+            SyntaxTree::Bounds(_, _, _) => {
+                Ok(Vec::new())
             },
-            SyntaxTree::Shrink(_, tree) => {
+            SyntaxTree::Shrink(_, tree, _) | SyntaxTree::Annotated(tree, _, _) => {
                 tree.compile(reference, bounds)
             },
-            SyntaxTree::Plus(trees) => {
-                trees.iter()
-                    .flat_map(|tree| tree.compile(reference, bounds))
-                    .collect()
-            },
-            SyntaxTree::Square(tree) => {
-                let w = bounds.2 - bounds.0;
-                let h = bounds.3 - bounds.1;
-                let bounds = if h > w {
-                    let d = (h - w) / 2.0;
-                    (bounds.0, bounds.1 + d, bounds.2, bounds.3 - d)
-                } else {
-                    let d = (w - h) / 2.0;
-                    (bounds.0 + d, bounds.1, bounds.2 - d, bounds.3)
-                };
-                tree.compile(reference, bounds)
+            SyntaxTree::Plus(trees, _) => {
+                let mut result = Vec::new();
+                for tree in trees {
+                    result.append(&mut tree.compile(reference, bounds)?);
+                }
+                Ok(result)
             },
-            SyntaxTree::HalfwaySquare(tree) => {
-                let w = bounds.2 - bounds.0;
-                let h = bounds.3 - bounds.1;
-                let bounds = if h > w {
-                    let d = (h - w) / 4.0;
-                    (bounds.0, bounds.1 + d, bounds.2, bounds.3 - d)
-                } else {
-                    let d = (w - h) / 4.0;
-                    (bounds.0 + d, bounds.1, bounds.2 - d, bounds.3)
-                };
-                tree.compile(reference, bounds)
+            SyntaxTree::Square(tree, _) => {
+                tree.compile(reference, squared(bounds, 2.0))
             },
-            SyntaxTree::Horizontal(trees) => {
+            SyntaxTree::HalfwaySquare(tree, _) => {
+                tree.compile(reference, squared(bounds, 4.0))
+            },
+            SyntaxTree::Horizontal(trees, _) => {
                 let total_weight: f32 = trees
                     .iter()
                     .map(SyntaxTree::weight)
@@ -66,12 +57,12 @@ impl SyntaxTree {
                     let weight = tree.weight();
                     let width = weight / total_weight * (bounds.2 - bounds.0);
                     let bounds = (x, bounds.1, x + width, bounds.3);
-                    result.append(&mut tree.compile(reference, bounds));
+                    result.append(&mut tree.compile(reference, bounds)?);
                     x += width;
                 }
-                result
+                Ok(result)
             }
-            SyntaxTree::Vertical(trees) => {
+            SyntaxTree::Vertical(trees, _) => {
                 let total_weight: f32 = trees
                     .iter()
                     .map(SyntaxTree::weight)
@@ -82,42 +73,184 @@ impl SyntaxTree {
                     let weight = tree.weight();
                     let height = weight / total_weight * (bounds.3 - bounds.1);
                     let bounds = (bounds.0, y, bounds.2, y + height);
-                    result.append(&mut tree.compile(reference, bounds));
+                    result.append(&mut tree.compile(reference, bounds)?);
                     y += height;
                 }
-                result
+                Ok(result)
+            },
+            SyntaxTree::Inner(outer, inner, _) => {
+                let (mut result, inner_bounds) = outer.compile_enclosure(reference, bounds)?;
+                result.append(&mut inner.compile(reference, inner_bounds)?);
+                Ok(result)
             },
-            SyntaxTree::Inner(box SyntaxTree::Bounds(start, end), tree) => {
-                // TODO: fit bounds inside current bounds
-                tree.compile(reference, (start.0, start.1, end.0, end.1))
+            SyntaxTree::Ident(ident, span) => {
+                Ok(fit_inside(lookup(reference, ident, *span)?.0.clone(), bounds))
             },
-            SyntaxTree::Inner(box SyntaxTree::Ident(outer), inner) => {
-                let (outer_lines, Annotations { inner: (outer_rect, inner_rect), .. }) = reference.get(outer).unwrap();
-                let outer = fit_inside(outer_lines.clone(), (outer_rect.0.0, outer_rect.0.1, outer_rect.1.0, outer_rect.1.1));
-                let inner = inner.compile(reference, (inner_rect.0.0, inner_rect.0.1, inner_rect.1.0, inner_rect.1.1));
-                let combined = [outer, inner].concat();
-                fit_inside(combined, bounds)
+        }
+    }
+
+    /// Compiles `self` as the outer part of `outer { inner }`, returning its lines and the bounds
+    /// the inner part is placed in. Named characters and `( ... ? {} := ...)` bring their own
+    /// annotation, anything else uses the default one.
+    fn compile_enclosure(&self, reference: &HashMap<String, (Vec<Path>, Annotations)>, bounds: Bounds) -> Result<(Vec<Path>, Bounds), Diagnostic> {
+        match self {
+            SyntaxTree::Bounds(start, end, _) => {
+                Ok((Vec::new(), relative_to((start.0, start.1, end.0, end.1), bounds)))
             },
-            SyntaxTree::Ident(ident) => {
-                // TODO: remove unwrap
-                fit_inside(reference.get(ident).unwrap().0.clone(), bounds)
+            SyntaxTree::Ident(outer, span) => {
+                let (outer_paths, Annotations { inner: (outer_rect, inner_rect), .. }) = lookup(reference, outer, *span)?;
+                let outer = fit_inside(outer_paths.clone(), relative_to((outer_rect.0.0, outer_rect.0.1, outer_rect.1.0, outer_rect.1.1), bounds));
+                Ok((outer, relative_to((inner_rect.0.0, inner_rect.0.1, inner_rect.1.0, inner_rect.1.1), bounds)))
+            },
+            SyntaxTree::Annotated(tree, Annotations { inner: (outer_rect, inner_rect), .. }, _) => {
+                let outer = tree.compile(reference, relative_to((outer_rect.0.0, outer_rect.0.1, outer_rect.1.0, outer_rect.1.1), bounds))?;
+                Ok((outer, relative_to((inner_rect.0.0, inner_rect.0.1, inner_rect.1.0, inner_rect.1.1), bounds)))
+            },
+            SyntaxTree::Shrink(_, tree, _) => {
+                tree.compile_enclosure(reference, bounds)
+            },
+            SyntaxTree::Square(tree, _) => {
+                tree.compile_enclosure(reference, squared(bounds, 2.0))
+            },
+            SyntaxTree::HalfwaySquare(tree, _) => {
+                tree.compile_enclosure(reference, squared(bounds, 4.0))
+            },
+            tree => {
+                let (_, inner_rect) = Annotations::new().inner;
+                let outer = tree.compile(reference, bounds)?;
+                Ok((outer, relative_to((inner_rect.0.0, inner_rect.0.1, inner_rect.1.0, inner_rect.1.1), bounds)))
             },
-            _ => todo!()
         }
     }
 }
 
-fn fit_inside(mut lines: Vec<((f32, f32), (f32, f32))>, bounds: (f32, f32, f32, f32)) -> Vec<((f32, f32), (f32, f32))> {
+/// Moves the longer sides of `bounds` towards a square, `2.0` all the way and `4.0` halfway.
+fn squared(bounds: Bounds, divisor: f32) -> Bounds {
+    let w = bounds.2 - bounds.0;
+    let h = bounds.3 - bounds.1;
+    if h > w {
+        let d = (h - w) / divisor;
+        (bounds.0, bounds.1 + d, bounds.2, bounds.3 - d)
+    } else {
+        let d = (w - h) / divisor;
+        (bounds.0 + d, bounds.1, bounds.2 - d, bounds.3)
+    }
+}
+
+/// Maps `rect`, given in unit coordinates, into `bounds`.
+fn relative_to(rect: Bounds, bounds: Bounds) -> Bounds {
+    let w = bounds.2 - bounds.0;
+    let h = bounds.3 - bounds.1;
+    (
+        bounds.0 + rect.0 * w,
+        bounds.1 + rect.1 * h,
+        bounds.0 + rect.2 * w,
+        bounds.1 + rect.3 * h,
+    )
+}
+
+pub(super) fn fit_inside(paths: Vec<Path>, bounds: Bounds) -> Vec<Path> {
     let x = bounds.0;
     let y = bounds.1;
     let w = bounds.2 - bounds.0;
     let h = bounds.3 - bounds.1;
-    lines.iter_mut()
-        .for_each(|(from, to)| {
-            from.0 = from.0 * w + x;
-            from.1 = from.1 * h + y;
-            to.0 = to.0 * w + x;
-            to.1 = to.1 * h + y;
-        });
-    lines
-}
\ No newline at end of file
+    paths
+        .into_iter()
+        .map(|path| path.map(|(px, py)| (px * w + x, py * h + y)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::{compile_glyphs, GlyphSet, Segment};
+
+    fn glyphs(src: &str) -> GlyphSet {
+        compile_glyphs(src).unwrap()
+    }
+
+    /// Start, control and end points of every segment of the glyph `name`.
+    fn points(glyphs: &GlyphSet, name: &str) -> Vec<(f32, f32)> {
+        glyphs
+            .get(name)
+            .unwrap()
+            .segments()
+            .flat_map(|segment| match *segment {
+                Segment::Line(start, end) => vec![start, end],
+                Segment::Cubic(p0, p1, p2, p3) => vec![p0, p1, p2, p3],
+            })
+            .collect()
+    }
+
+    fn assert_points(actual: &[(f32, f32)], expected: &[(f32, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a.0 - e.0).abs() < 1e-5 && (a.1 - e.1).abs() < 1e-5, "{actual:?} instead of {expected:?}");
+        }
+    }
+
+    #[test]
+    fn standalone_bounds_only_apply_on_their_own() {
+        let glyphs = glyphs("hat := 0 0 -> 1 1 ? := 0 0.25 => 1 1.25;\nboth := hat | hat;\nplain := 0 0.5 -> 1 0.5;");
+        assert_points(&points(&glyphs, "hat"), &[(0.0, 0.25), (1.0, 1.25)]);
+        assert_eq!(glyphs.get("hat").unwrap().bounds, Some((0.0, 0.25, 1.0, 1.25)));
+        assert_eq!(glyphs.get("hat").unwrap().annotations.standalone, ((0.0, 0.25), (1.0, 1.25)));
+
+        // used as a component, the glyph fills the unit square of its cell
+        assert_points(&points(&glyphs, "both"), &[(0.0, 0.0), (0.5, 1.0), (0.5, 0.0), (1.0, 1.0)]);
+        assert_eq!(glyphs.get("both").unwrap().bounds, Some((0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(glyphs.get("plain").unwrap().bounds, Some((0.0, 0.5, 1.0, 0.5)));
+    }
+
+    #[test]
+    fn inner_boxes_are_relative_to_their_cell() {
+        let glyphs = glyphs("x := 0 0 -> 1 1;\nleft := 0.2 0.2 => 0.8 0.8 { x } | x;\nsquared := [[0 0 => 0.5 0.5 { x }]] / x;");
+        assert_points(&points(&glyphs, "left"), &[(0.1, 0.2), (0.4, 0.8), (0.5, 0.0), (1.0, 1.0)]);
+        // the top half squared is 0.25 0 => 0.75 0.5, the box takes its upper left quarter
+        assert_points(&points(&glyphs, "squared"), &[(0.25, 0.0), (0.5, 0.25), (0.0, 0.5), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn enclosures_default_to_the_middle() {
+        let glyphs = glyphs("x := 0 0 -> 1 1;\npair := (x|x) { x };\nsquared := [[x|x]] { x } | x;");
+        assert_points(&points(&glyphs, "pair"), &[(0.0, 0.0), (0.5, 1.0), (0.5, 0.0), (1.0, 1.0), (0.2, 0.2), (0.8, 0.8)]);
+        // the outer is squared within the left half, the inner part follows it
+        assert_points(&points(&glyphs, "squared"), &[
+            (0.0, 0.25), (0.25, 0.75), (0.25, 0.25), (0.5, 0.75),
+            (0.1, 0.35), (0.4, 0.65),
+            (0.5, 0.0), (1.0, 1.0),
+        ]);
+    }
+
+    #[test]
+    fn annotated_outers_bring_their_own_inner_box() {
+        let src = "x := 0 0 -> 1 1;\n\
+            hat := x ? {} := 0 0 => 1 0.5 + 0.25 0.5 => 0.75 1;\n\
+            named := hat { x };\n\
+            inline := (x ? {} := 0 0 => 1 0.5 + 0 0.5 => 1 1) { x };\n\
+            shrunk := &(x ? {} := 0 0 => 1 0.5 + 0 0.5 => 1 1) { x } | x;";
+        let glyphs = glyphs(src);
+        assert_points(&points(&glyphs, "named"), &[(0.0, 0.0), (1.0, 0.5), (0.25, 0.5), (0.75, 1.0)]);
+        assert_points(&points(&glyphs, "inline"), &[(0.0, 0.0), (1.0, 0.5), (0.0, 0.5), (1.0, 1.0)]);
+        assert_points(&points(&glyphs, "shrunk"), &[(0.0, 0.0), (1.0 / 3.0, 0.5), (0.0, 0.5), (1.0 / 3.0, 1.0), (1.0 / 3.0, 0.0), (1.0, 1.0)]);
+    }
+
+    #[test]
+    fn curves_are_transformed_by_their_control_points() {
+        let glyphs = glyphs("c := 0 0 ~> 0.5 0 ~> 1 0.5 -> 1 1 -> 0 1;\nd := c | [[c]];");
+        let c = glyphs.get("c").unwrap();
+        assert_eq!(c.paths.len(), 1);
+        assert_eq!(c.paths[0].segments, [
+            Segment::Cubic((0.0, 0.0), (0.5, 0.0), (1.0, 0.5), (1.0, 1.0)),
+            Segment::Line((1.0, 1.0), (0.0, 1.0)),
+        ]);
+
+        // still one cubic and one line per path, nothing is flattened along the way
+        let d = glyphs.get("d").unwrap();
+        assert_eq!(d.paths.len(), 2);
+        assert!(d.paths.iter().all(|path| matches!(path.segments[..], [Segment::Cubic(..), Segment::Line(..)])));
+        assert_points(&points(&glyphs, "d"), &[
+            (0.0, 0.0), (0.25, 0.0), (0.5, 0.5), (0.5, 1.0), (0.5, 1.0), (0.0, 1.0),
+            (0.5, 0.25), (0.75, 0.25), (1.0, 0.5), (1.0, 0.75), (1.0, 0.75), (0.5, 0.75),
+        ]);
+    }
+}
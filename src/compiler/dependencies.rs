@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::parser::character::Character;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unvisited,
+    InProgress,
+    Done,
+    Failed,
+}
+
+struct Graph<'a> {
+    characters: &'a [Character],
+    indices: HashMap<&'a str, usize>,
+    states: Vec<State>,
+    stack: Vec<usize>,
+    order: Vec<usize>,
+}
+
+/// Orders `characters` so that every character comes after the characters it references.
+/// Redefined names, undefined names and cycles are reported, and the characters involved (as well
+/// as everything depending on them) are left out of the order.
+pub fn dependency_order(characters: &[Character], diagnostics: &mut Vec<Diagnostic>) -> Vec<usize> {
+    let mut graph = Graph {
+        characters,
+        indices: HashMap::new(),
+        states: vec![State::Unvisited; characters.len()],
+        stack: Vec::new(),
+        order: Vec::new(),
+    };
+
+    for (index, character) in characters.iter().enumerate() {
+        for (name, span) in character.names.iter().zip(&character.name_spans) {
+            match graph.indices.get(name.as_str()) {
+                Some(&previous) => {
+                    let previous = characters[previous].names
+                        .iter()
+                        .position(|it| it == name)
+                        .map(|position| characters[previous].name_spans[position])
+                        .unwrap();
+                    let kind = ErrorKind::DuplicateName { name: name.clone(), previous };
+                    diagnostics.push(Diagnostic::new(kind, *span));
+                    graph.states[index] = State::Failed;
+                },
+                None => {
+                    graph.indices.insert(name, index);
+                },
+            }
+        }
+    }
+
+    for index in 0..characters.len() {
+        graph.visit(index, diagnostics);
+    }
+
+    graph.order
+}
+
+impl Graph<'_> {
+    fn visit(&mut self, index: usize, diagnostics: &mut Vec<Diagnostic>) -> State {
+        if self.states[index] != State::Unvisited {
+            return self.states[index];
+        }
+
+        self.states[index] = State::InProgress;
+        self.stack.push(index);
+
+        let mut failed = false;
+        for (name, span) in self.characters[index].syntax_tree.references() {
+            let Some(&dependency) = self.indices.get(name) else {
+                diagnostics.push(Diagnostic::new(ErrorKind::UndefinedName(name.to_string()), span));
+                failed = true;
+                continue;
+            };
+            match self.visit(dependency, diagnostics) {
+                State::InProgress => {
+                    let start = self.stack.iter().position(|it| *it == dependency).unwrap();
+                    let cycle = self.stack[start..]
+                        .iter()
+                        .chain([&dependency])
+                        .map(|it| self.characters[*it].names[0].clone())
+                        .collect();
+                    diagnostics.push(Diagnostic::new(ErrorKind::DependencyCycle(cycle), span));
+                    failed = true;
+                },
+                State::Failed => failed = true,
+                _ => {},
+            }
+        }
+
+        self.stack.pop();
+        self.states[index] = if failed {
+            State::Failed
+        } else {
+            self.order.push(index);
+            State::Done
+        };
+        self.states[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    /// The first names in dependency order, and the diagnostics.
+    fn order(src: &str) -> (Vec<String>, Vec<Diagnostic>) {
+        let (characters, mut diagnostics) = parse(src);
+        assert!(diagnostics.is_empty());
+        let order = dependency_order(&characters, &mut diagnostics);
+        (order.into_iter().map(|index| characters[index].names[0].clone()).collect(), diagnostics)
+    }
+
+    #[test]
+    fn references_come_first() {
+        let (order, diagnostics) = order("c := a | b;\nb := [a];\na := 0 0 -> 1 1;\nd := 0 0 -> 1 0;");
+        assert_eq!(order, ["a", "b", "c", "d"]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn aliases_are_references_too() {
+        let (order, diagnostics) = order("b := alias / alias;\na, alias := 0 0 -> 1 1;");
+        assert_eq!(order, ["a", "b"]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn cycles_are_reported_along_their_names() {
+        let (order, diagnostics) = order("a := b;\nb := c | 0 0 -> 1 1;\nc := a;\nd := a;\ne := 0 0 -> 1 1;\nf := f;");
+        assert_eq!(order, ["e"]);
+        let kinds: Vec<&ErrorKind> = diagnostics.iter().map(|diagnostic| &diagnostic.kind).collect();
+        assert_eq!(kinds, [
+            &ErrorKind::DependencyCycle(vec![String::from("a"), String::from("b"), String::from("c"), String::from("a")]),
+            &ErrorKind::DependencyCycle(vec![String::from("f"), String::from("f")]),
+        ]);
+        assert_eq!((diagnostics[0].line(), diagnostics[0].column()), (3, 6));
+    }
+
+    #[test]
+    fn undefined_names_fail_their_dependents() {
+        let (order, diagnostics) = order("a := missing + 0 0 -> 1 1;\nb := a;\nc := 0 0 -> 1 1;");
+        assert_eq!(order, ["c"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ErrorKind::UndefinedName(String::from("missing")));
+        assert_eq!((diagnostics[0].span.start, diagnostics[0].span.end), (5, 12));
+    }
+
+    #[test]
+    fn redefinitions_point_at_the_first_definition() {
+        let src = "a := 0 0 -> 1 1;\nb, c := a;\nb := 0 0 -> 1 0;\nd, a := 0 1 -> 1 1;\ne := b;";
+        let (order, diagnostics) = order(src);
+        assert_eq!(order, ["a", "b", "e"]);
+        assert_eq!(diagnostics.len(), 2);
+        for (diagnostic, (line, previous_line, name)) in diagnostics.iter().zip([(3, 2, "b"), (4, 1, "a")]) {
+            let ErrorKind::DuplicateName { name: duplicate, previous } = &diagnostic.kind else {
+                panic!("unexpected {diagnostic}");
+            };
+            assert_eq!(duplicate, name);
+            assert_eq!(diagnostic.line(), line);
+            assert_eq!(previous.line, previous_line);
+            assert_eq!(previous.text(src), name);
+        }
+    }
+}
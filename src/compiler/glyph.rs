@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::slice;
+use crate::compiler::path::enclosing;
+use crate::compiler::{Path, Segment};
+use crate::lexer::Span;
+use crate::parser::{Annotations, Character, SyntaxTree};
+
+/// A compiled character.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyph {
+    /// The name followed by its aliases, as written in the definition.
+    pub names: Vec<String>,
+    /// Marked with `§`.
+    pub is_radical: bool,
+    /// The strokes and radicals to type, in order.
+    pub input: Vec<String>,
+    /// In the glyph's own coordinates, i.e. already placed into its standalone bounds.
+    pub paths: Vec<Path>,
+    /// Tight bounding box `(min_x, min_y, max_x, max_y)` of the paths, `None` if there are none.
+    pub bounds: Option<(f32, f32, f32, f32)>,
+    /// The `? ...` annotations of the definition: the standalone bounds the paths were placed
+    /// into, and where the inner part goes when the glyph encloses another.
+    pub annotations: Annotations,
+    /// Everything after the `:=`, as parsed.
+    pub syntax_tree: SyntaxTree,
+    /// The whole definition in the source.
+    pub span: Span,
+}
+
+impl Glyph {
+    pub(crate) fn new(character: &Character, input: Vec<String>, paths: Vec<Path>) -> Self {
+        let bounds = paths.iter().filter_map(Path::bounds).reduce(enclosing);
+        Glyph {
+            names: character.names.clone(),
+            is_radical: character.is_radical,
+            input,
+            paths,
+            bounds,
+            annotations: character.annotations(),
+            syntax_tree: character.syntax_tree.clone(),
+            span: character.span,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.names[0]
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.names[1..]
+    }
+
+    /// All segments of all paths, in order.
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.paths.iter().flat_map(|path| &path.segments)
+    }
+}
+
+/// The glyphs of a file in definition order, which can be looked up by name or alias.
+#[derive(Debug, Clone, Default)]
+pub struct GlyphSet {
+    glyphs: Vec<Glyph>,
+    by_name: HashMap<String, usize>,
+}
+
+impl GlyphSet {
+    pub(crate) fn new(glyphs: Vec<Glyph>) -> Self {
+        let mut by_name = HashMap::new();
+        for (idx, glyph) in glyphs.iter().enumerate() {
+            for name in &glyph.names {
+                by_name.insert(name.clone(), idx);
+            }
+        }
+        GlyphSet { glyphs, by_name }
+    }
+
+    /// Looks up a glyph by its name or any of its aliases.
+    pub fn get(&self, name: &str) -> Option<&Glyph> {
+        self.by_name.get(name).map(|idx| &self.glyphs[*idx])
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Glyph> {
+        self.glyphs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a GlyphSet {
+    type Item = &'a Glyph;
+    type IntoIter = slice::Iter<'a, Glyph>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.glyphs.iter()
+    }
+}
+
+impl IntoIterator for GlyphSet {
+    type Item = Glyph;
+    type IntoIter = std::vec::IntoIter<Glyph>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.glyphs.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::compile_glyphs;
+
+    #[test]
+    fn lookup_by_name_and_alias() {
+        let glyphs = compile_glyphs("a, alias := 0 0 -> 1 1;\nb := a;").unwrap();
+        assert_eq!(glyphs.len(), 2);
+        assert_eq!(glyphs.get("alias").unwrap().name(), "a");
+        assert_eq!(glyphs.get("b").unwrap().aliases(), &[] as &[String]);
+        assert!(glyphs.get("c").is_none());
+        assert!(glyphs.get("A").is_none());
+    }
+
+    #[test]
+    fn definition_order() {
+        // compiled dependencies first, but listed as written
+        let src = "top := middle / bottom;\nmiddle := bottom | bottom;\nbottom := 0 0 -> 1 0;";
+        let glyphs = compile_glyphs(src).unwrap();
+        let names: Vec<_> = glyphs.iter().map(|glyph| glyph.name()).collect();
+        assert_eq!(names, ["top", "middle", "bottom"]);
+        let owned: Vec<_> = glyphs.into_iter().map(|glyph| glyph.names).collect();
+        assert_eq!(owned, [["top"], ["middle"], ["bottom"]]);
+        assert!(compile_glyphs("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn bounds_of_the_paths() {
+        let glyphs = compile_glyphs("a := 0.1 0.2 -> 0.6 0.3 + 0.5 0 ~> 1 0.5 -> 0.5 1;\nspace := 0 0 => 1 1;").unwrap();
+        let (min_x, min_y, max_x, max_y) = glyphs.get("a").unwrap().bounds.unwrap();
+        // the curve only reaches 0.75 of the way to its control point
+        assert_eq!((min_x, min_y, max_y), (0.1, 0.0, 1.0));
+        assert!((max_x - 0.75).abs() < 1e-6);
+        assert_eq!(glyphs.get("space").unwrap().bounds, None);
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
+use crate::compiler::lookup;
+use crate::diagnostic::Diagnostic;
 use crate::parser::character::Character;
 use crate::parser::SyntaxTree;
 
 impl Character {
-    pub fn get_input(&self, reference: &HashMap<String, Vec<String>>) -> Vec<String> {
+    pub(crate) fn get_input(&self, reference: &HashMap<String, Vec<String>>) -> Result<Vec<String>, Diagnostic> {
         if self.is_radical {
-            vec![self.names[0].clone()]
+            Ok(vec![self.names[0].clone()])
         } else {
             self.syntax_tree.get_input(reference)
         }
@@ -13,22 +15,25 @@ impl Character {
 }
 
 impl SyntaxTree {
-    fn get_input(&self, reference: &HashMap<String, Vec<String>>) -> Vec<String> {
+    fn get_input(&self, reference: &HashMap<String, Vec<String>>) -> Result<Vec<String>, Diagnostic> {
         use SyntaxTree as T;
         match self {
-            T::Ident(ident) => {
-                // TODO: get rid of unwrap
-                reference.get(ident).unwrap().clone()
+            T::Ident(ident, span) => {
+                Ok(lookup(reference, ident, *span)?.clone())
             }
-            T::Lines(_) => vec![String::from("stroke")],
-            T::Bounds(_, _) => Vec::new(),
-            T::Plus(parts) | T::Horizontal(parts) | T::Vertical(parts) => {
-                parts.iter().flat_map(|tree| tree.get_input(reference)).collect()
+            T::Lines(_, _) => Ok(vec![String::from("stroke")]),
+            T::Bounds(_, _, _) => Ok(Vec::new()),
+            T::Plus(parts, _) | T::Horizontal(parts, _) | T::Vertical(parts, _) => {
+                let mut result = Vec::new();
+                for tree in parts {
+                    result.append(&mut tree.get_input(reference)?);
+                }
+                Ok(result)
             },
-            T::Inner(outer, inner) => {
-                [outer.get_input(reference), inner.get_input(reference)].concat()
+            T::Inner(outer, inner, _) => {
+                Ok([outer.get_input(reference)?, inner.get_input(reference)?].concat())
             }
-            T::Shrink(_, tree) | T::Square(tree) | T::HalfwaySquare(tree) => {
+            T::Shrink(_, tree, _) | T::Square(tree, _) | T::HalfwaySquare(tree, _) | T::Annotated(tree, _, _) => {
                 tree.get_input(reference)
             },
         }
@@ -1,83 +1,124 @@
 mod compiler;
+mod dependencies;
+mod glyph;
 mod input;
+mod path;
+mod segment;
 
 use std::collections::HashMap;
-use crate::lexer::Lexer;
-use crate::parser::character::Annotations;
-use crate::parser::Parser;
+use crate::compiler::compiler::fit_inside;
+use crate::compiler::dependencies::dependency_order;
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::lexer::Span;
+use crate::parser::{parse, Annotations};
 
+pub use glyph::{Glyph, GlyphSet};
+pub use path::Path;
+pub use segment::{flatten, Segment};
 
-type Line = ((f32, f32), (f32, f32));
-type Rect = (f32, f32, f32, f32);
+/// Straight line, as produced by [`flatten`].
+pub type Line = ((f32, f32), (f32, f32));
+/// Keyed by the first name, holding all names (first name and aliases), the input sequence and the segments.
+type Compiled = HashMap<String, (Vec<String>, Vec<String>, Vec<Segment>)>;
 
-pub fn compile(src: &str) -> HashMap<String, (Vec<String>, Vec<Line>)> {
-    let lexer = Lexer::new(src);
-    let mut parser = Parser::new(lexer.peekable());
-    let mut map: HashMap<String, (Vec<Line>, Annotations)> = HashMap::new();
+/// Compiles every character, keyed by its first name. See [`compile_glyphs`] for the typed
+/// result in definition order.
+pub fn compile(src: &str) -> Result<Compiled, Vec<Diagnostic>> {
+    let glyphs = compile_glyphs(src)?;
+    Ok(glyphs
+        .into_iter()
+        .map(|glyph| {
+            let segments = glyph.segments().copied().collect();
+            (glyph.names[0].clone(), (glyph.names, glyph.input, segments))
+        })
+        .collect())
+}
+
+/// Compiles every character into a [`GlyphSet`], or reports all problems found in `src`,
+/// sorted by their position.
+pub fn compile_glyphs(src: &str) -> Result<GlyphSet, Vec<Diagnostic>> {
+    let (glyphs, diagnostics) = compile_partial(src);
+    match diagnostics.is_empty() {
+        true => Ok(glyphs),
+        false => Err(diagnostics),
+    }
+}
+
+/// Compiles what it can, along with the problems found in `src` sorted by their position.
+/// Characters that fail, or use one that fails, are left out.
+pub fn compile_partial(src: &str) -> (GlyphSet, Vec<Diagnostic>) {
+    let (characters, mut diagnostics) = parse(src);
+    let mut map: HashMap<String, (Vec<Path>, Annotations)> = HashMap::new();
     let mut inputs: HashMap<String, Vec<String>> = HashMap::new();
 
-    while let Some(mut next) = parser.next() {
-        let input = next.get_input(&inputs);
-        inputs.insert(next.names[0].clone(), input);
+    let mut glyphs: Vec<Option<Glyph>> = vec![None; characters.len()];
+    for index in dependency_order(&characters, &mut diagnostics) {
+        let next = &characters[index];
 
-        let lines = next.syntax_tree.compile(&map, (0.0, 0.0, 1.0, 1.0));
-        map.insert(next.names.remove(0), (lines, next.annotations));
+        let compiled = next.get_input(&inputs)
+            .and_then(|input| Ok((input, next.syntax_tree.compile(&map, (0.0, 0.0, 1.0, 1.0))?)));
+        match compiled {
+            Ok((input, paths)) => {
+                let annotations = next.annotations();
+                for name in &next.names {
+                    inputs.insert(name.clone(), input.clone());
+                    map.insert(name.clone(), (paths.clone(), annotations));
+                }
+
+                // components are compiled into the unit square, only glyphs emitted on their own get their standalone bounds
+                let (start, end) = annotations.standalone;
+                let paths = fit_inside(paths, (start.0, start.1, end.0, end.1));
+                glyphs[index] = Some(Glyph::new(next, input, paths));
+            },
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
     }
 
-    // TODO: apply standalone bounds
-    map
-        .into_iter()
-        .map(|(k, (v, _))| {
-            let i = inputs.remove(&k).unwrap();
-            (k, (i, v))
-        })
-        .collect()
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    (GlyphSet::new(glyphs.into_iter().flatten().collect()), diagnostics)
 }
 
-fn interpolate(progress: f32, dots: &[(f32, f32)]) -> Option<(f32, f32)> {
-    match dots {
-        [] => None,
-        [xy] => Some(*xy),
-        dots => {
-            let dots: Vec<_> = (1..dots.len())
-                .map(|idx| (
-                    dots[idx-1].0 * (1.0 - progress) + dots[idx].0 * progress,
-                    dots[idx-1].1 * (1.0 - progress) + dots[idx].1 * progress,
-                ))
-                .collect();
-            interpolate(progress, &dots)
-        }
-    }
+fn lookup<'a, T>(reference: &'a HashMap<String, T>, name: &str, span: Span) -> Result<&'a T, Diagnostic> {
+    reference
+        .get(name)
+        .ok_or_else(|| Diagnostic::new(ErrorKind::UndefinedName(name.to_string()), span))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn compile_smooth_line(dots: &[(f32, f32)]) -> Vec<Line> {
-    if let [start, end] = dots {
-        return vec![(*start, *end)];
+    #[test]
+    fn aliases_resolve_to_the_same_glyph() {
+        let src = "§good, yin := 0 0 -> 1 1;\nby-alias := yin | 0 0 -> 1 0;\nby-name := good | 0 0 -> 1 0;";
+        let glyphs = compile_glyphs(src).unwrap();
+        let good = glyphs.get("good").unwrap();
+        assert!(std::ptr::eq(good, glyphs.get("yin").unwrap()));
+        assert_eq!((good.name(), good.aliases()), ("good", &[String::from("yin")][..]));
+
+        let (by_alias, by_name) = (glyphs.get("by-alias").unwrap(), glyphs.get("by-name").unwrap());
+        assert_eq!(by_alias.paths, by_name.paths);
+        assert_eq!(by_alias.input, ["good", "stroke"]);
+        assert_eq!(by_alias.input, by_name.input);
+
+        let compiled = compile(src).unwrap();
+        assert_eq!(compiled["good"].0, ["good", "yin"]);
+        assert!(!compiled.contains_key("yin"));
     }
 
-    const LINE_STEPS: usize = 20;
-    (0..LINE_STEPS + 1)
-        .flat_map(|idx| interpolate(idx as f32 / LINE_STEPS as f32, dots))
-        .collect::<Vec<_>>()
-        .windows(2)
-        .map(|xyxy| (xyxy[0], xyxy[1]))
-        .collect()
+    #[test]
+    fn duplicate_aliases_are_reported() {
+        let src = "a, b := 0 0 -> 1 1;\nc, b := 0 0 -> 1 0;\nd := b;";
+        let (glyphs, diagnostics) = compile_partial(src);
+        assert_eq!(diagnostics.len(), 1);
+        let ErrorKind::DuplicateName { name, previous } = &diagnostics[0].kind else {
+            panic!("unexpected {}", diagnostics[0]);
+        };
+        assert_eq!((name.as_str(), previous.line, diagnostics[0].line()), ("b", 1, 2));
+        // the second definition is left out, `b` stays the alias of `a`
+        let names: Vec<_> = glyphs.iter().map(Glyph::name).collect();
+        assert_eq!(names, ["a", "d"]);
+        assert_eq!(glyphs.get("d").unwrap().paths, glyphs.get("a").unwrap().paths);
+        assert!(glyphs.get("c").is_none());
+    }
 }
-
-/*
-def step(m: float, points: [(float, float)]):
-    if len(points) == 1:
-        return points[0]
-    else:
-        result = []
-        for idx in range(len(points)-1):
-            result.append((points[idx][0] * (1-m) + points[idx+1][0] * m, points[idx][1] * (1-m) + points[idx+1][1] * m))
-        return step(m, result)
-
-def round_line(steps: [(float, float)]) -> [(float, float)]:
-    result = []
-    for i in range(101):
-        result.append(step(i/100, steps))
-    return result
- */
\ No newline at end of file
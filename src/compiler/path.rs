@@ -0,0 +1,47 @@
+use crate::compiler::{Line, Segment};
+
+/// Segments drawn in one go, each one starting where the previous one ended. Every `->` chain
+/// in the source is one path; `+`, `|` and the other operators only put paths next to each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn start(&self) -> Option<(f32, f32)> {
+        self.segments.first().map(Segment::start)
+    }
+
+    pub fn end(&self) -> Option<(f32, f32)> {
+        self.segments.last().map(Segment::end)
+    }
+
+    /// Whether the path ends where it started, so it gets a join there instead of two caps.
+    pub fn is_closed(&self) -> bool {
+        self.segments.len() > 1 && self.start() == self.end()
+    }
+
+    /// Tight bounding box `(min_x, min_y, max_x, max_y)`, `None` for an empty path.
+    pub fn bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        self.segments.iter().map(Segment::bounds).reduce(enclosing)
+    }
+
+    /// The path with every point passed through `f`, see [`Segment::map`].
+    pub fn map(self, f: impl Fn((f32, f32)) -> (f32, f32)) -> Path {
+        Path { segments: self.segments.into_iter().map(|segment| segment.map(&f)).collect() }
+    }
+
+    /// Points of the path with its curves flattened to within `tolerance`, see [`Segment::flatten_with`].
+    pub fn flatten(&self, tolerance: f32) -> Vec<(f32, f32)> {
+        let mut result: Vec<(f32, f32)> = self.start().into_iter().collect();
+        for segment in &self.segments {
+            segment.flatten_with(tolerance, &mut |(_, to): Line| result.push(to));
+        }
+        result
+    }
+}
+
+/// Bounding box around both boxes.
+pub(crate) fn enclosing(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+}
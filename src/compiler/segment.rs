@@ -0,0 +1,337 @@
+use crate::compiler::Line;
+
+type Point = (f32, f32);
+
+/// Largest distance allowed between a curve of degree four or higher and the cubics replacing it.
+const CUBIC_TOLERANCE: f32 = 5e-4;
+/// Limits how often a curve of degree four or higher is halved while looking for cubics.
+const MAX_SPLITS: u32 = 8;
+/// Limits how often a cubic is halved while flattening, i.e. at most 2^16 lines per curve.
+const MAX_DEPTH: u32 = 16;
+
+/// A piece of a stroke, in the same coordinates as the rest of the glyph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line(Point, Point),
+    /// Cubic Bézier curve given by its start, two control points and its end.
+    Cubic(Point, Point, Point, Point),
+}
+
+impl Segment {
+    /// The Bézier curve through the control polygon `points`. Quadratic curves are raised to
+    /// cubics exactly, curves of higher degree are split into cubics that approximate them.
+    pub(crate) fn from_control_points(points: &[Point]) -> Vec<Segment> {
+        match *points {
+            [] | [_] => Vec::new(),
+            [start, end] => vec![Segment::Line(start, end)],
+            [start, control, end] => vec![Segment::Cubic(
+                start,
+                lerp(start, control, 2.0 / 3.0),
+                lerp(end, control, 2.0 / 3.0),
+                end,
+            )],
+            [p0, p1, p2, p3] => vec![Segment::Cubic(p0, p1, p2, p3)],
+            _ => {
+                let mut result = Vec::new();
+                approximate(points, MAX_SPLITS, &mut result);
+                result
+            },
+        }
+    }
+
+    pub fn start(&self) -> Point {
+        match *self {
+            Segment::Line(start, _) | Segment::Cubic(start, _, _, _) => start,
+        }
+    }
+
+    pub fn end(&self) -> Point {
+        match *self {
+            Segment::Line(_, end) | Segment::Cubic(_, _, _, end) => end,
+        }
+    }
+
+    /// The segment with every point passed through `f`, which has to be an affine transformation
+    /// for curves to keep their shape.
+    pub fn map(self, f: impl Fn(Point) -> Point) -> Segment {
+        match self {
+            Segment::Line(start, end) => Segment::Line(f(start), f(end)),
+            Segment::Cubic(p0, p1, p2, p3) => Segment::Cubic(f(p0), f(p1), f(p2), f(p3)),
+        }
+    }
+
+    pub fn point_at(&self, t: f32) -> Point {
+        match *self {
+            Segment::Line(start, end) => lerp(start, end, t),
+            Segment::Cubic(p0, p1, p2, p3) => {
+                let (a, b, c) = (lerp(p0, p1, t), lerp(p1, p2, t), lerp(p2, p3, t));
+                lerp(lerp(a, b, t), lerp(b, c, t), t)
+            },
+        }
+    }
+
+    /// Tight bounding box `(min_x, min_y, max_x, max_y)` of the drawn segment, control points
+    /// of curves only count where the curve actually reaches.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let (start, end) = (self.start(), self.end());
+        let mut result = (start.0.min(end.0), start.1.min(end.1), start.0.max(end.0), start.1.max(end.1));
+        if let Segment::Cubic(p0, p1, p2, p3) = *self {
+            let x = extrema(p0.0, p1.0, p2.0, p3.0);
+            let y = extrema(p0.1, p1.1, p2.1, p3.1);
+            for t in x.into_iter().chain(y).flatten() {
+                let (x, y) = self.point_at(t);
+                result = (result.0.min(x), result.1.min(y), result.2.max(x), result.3.max(y));
+            }
+        }
+        result
+    }
+
+    /// Appends straight lines to `result` that stay within `tolerance` of the segment, see
+    /// [`Segment::flatten_with`].
+    pub fn flatten(&self, tolerance: f32, result: &mut Vec<Line>) {
+        self.flatten_with(tolerance, &mut |line| result.push(line));
+    }
+
+    /// Passes straight lines that stay within `tolerance` of the segment to `line`, in order.
+    /// Curves are halved until their control points are no further than `tolerance` from the
+    /// chord, so flat parts get few lines and tight bends many. The tolerance is measured in
+    /// the coordinates of the segment, i.e. after the glyph has been fitted into its bounds; for
+    /// output at another scale it has to be divided by that scale.
+    pub fn flatten_with(&self, tolerance: f32, line: &mut impl FnMut(Line)) {
+        match *self {
+            Segment::Line(start, end) => line((start, end)),
+            Segment::Cubic(p0, p1, p2, p3) => flatten_cubic([p0, p1, p2, p3], tolerance, MAX_DEPTH, line),
+        }
+    }
+}
+
+/// Flattens all segments into straight lines within `tolerance` of the curves, see [`Segment::flatten_with`].
+pub fn flatten(segments: &[Segment], tolerance: f32) -> Vec<Line> {
+    let mut result = Vec::with_capacity(segments.len());
+    for segment in segments {
+        segment.flatten(tolerance, &mut result);
+    }
+    result
+}
+
+fn flatten_cubic(points: [Point; 4], tolerance: f32, depth: u32, line: &mut impl FnMut(Line)) {
+    let [p0, p1, p2, p3] = points;
+    // the curve stays inside the hull of its control points, so they bound its distance from the chord
+    if depth == 0 || distance_to_chord(p1, p0, p3).max(distance_to_chord(p2, p0, p3)) <= tolerance {
+        line((p0, p3));
+        return;
+    }
+    let (a, b, c) = (lerp(p0, p1, 0.5), lerp(p1, p2, 0.5), lerp(p2, p3, 0.5));
+    let (ab, bc) = (lerp(a, b, 0.5), lerp(b, c, 0.5));
+    let middle = lerp(ab, bc, 0.5);
+    flatten_cubic([p0, a, ab, middle], tolerance, depth - 1, line);
+    flatten_cubic([middle, bc, c, p3], tolerance, depth - 1, line);
+}
+
+/// Distance of `point` from the line segment between `start` and `end`.
+fn distance_to_chord(point: Point, start: Point, end: Point) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = (start.0 + dx * t, start.1 + dy * t);
+    (point.0 - closest.0).hypot(point.1 - closest.1)
+}
+
+/// Replaces the curve with the control polygon `points` by cubics with the same end points and
+/// end tangents, halving it where a single cubic strays too far.
+fn approximate(points: &[Point], splits: u32, result: &mut Vec<Segment>) {
+    let degree = (points.len() - 1) as f32;
+    let (start, end) = (points[0], points[points.len() - 1]);
+    let (second, before_end) = (points[1], points[points.len() - 2]);
+    let cubic = Segment::Cubic(
+        start,
+        lerp(start, second, degree / 3.0),
+        lerp(end, before_end, degree / 3.0),
+        end,
+    );
+
+    const SAMPLES: usize = 8;
+    let mut scratch = points.to_vec();
+    let fits = (1..SAMPLES).all(|idx| {
+        let t = idx as f32 / SAMPLES as f32;
+        let (x0, y0) = de_casteljau(points, t, &mut scratch);
+        let (x1, y1) = cubic.point_at(t);
+        (x1 - x0).hypot(y1 - y0) <= CUBIC_TOLERANCE
+    });
+    if fits || splits == 0 {
+        result.push(cubic);
+        return;
+    }
+
+    let (first, second) = split(points);
+    approximate(&first, splits - 1, result);
+    approximate(&second, splits - 1, result);
+}
+
+/// Point at `t` on the curve with the control polygon `points`, `scratch` is overwritten.
+fn de_casteljau(points: &[Point], t: f32, scratch: &mut [Point]) -> Point {
+    scratch.copy_from_slice(points);
+    for len in (1..points.len()).rev() {
+        for idx in 0..len {
+            scratch[idx] = lerp(scratch[idx], scratch[idx + 1], t);
+        }
+    }
+    scratch[0]
+}
+
+/// The control polygons of the two halves of the curve.
+fn split(points: &[Point]) -> (Vec<Point>, Vec<Point>) {
+    let mut first = Vec::with_capacity(points.len());
+    let mut second = Vec::with_capacity(points.len());
+    let mut scratch = points.to_vec();
+    for len in (0..points.len()).rev() {
+        first.push(scratch[0]);
+        second.push(scratch[len]);
+        for idx in 0..len {
+            scratch[idx] = lerp(scratch[idx], scratch[idx + 1], 0.5);
+        }
+    }
+    second.reverse();
+    (first, second)
+}
+
+/// Parameters in `0..1` where one coordinate of a cubic turns around.
+fn extrema(p0: f32, p1: f32, p2: f32, p3: f32) -> [Option<f32>; 2] {
+    // derivative divided by 3: a t² + b t + c
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+    let inside = |t: f32| (t > 0.0 && t < 1.0).then_some(t);
+    if a.abs() < 1e-9 {
+        return [(b.abs() >= 1e-9).then(|| -c / b).and_then(inside), None];
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return [None, None];
+    }
+    let root = discriminant.sqrt();
+    [inside((-b + root) / (2.0 * a)), inside((-b - root) / (2.0 * a))]
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quarter circle of radius one, as the usual cubic approximation.
+    const ARC: Segment = Segment::Cubic((1.0, 0.0), (1.0, 0.552_284_8), (0.552_284_8, 1.0), (0.0, 1.0));
+
+    fn distance(a: Point, b: Point) -> f32 {
+        (b.0 - a.0).hypot(b.1 - a.1)
+    }
+
+    #[test]
+    fn lines_stay_lines() {
+        let line = Segment::Line((0.0, 0.0), (1.0, 0.5));
+        assert_eq!(flatten(&[line], 1e-3), [((0.0, 0.0), (1.0, 0.5))]);
+    }
+
+    #[test]
+    fn flat_curves_become_one_line() {
+        let curve = Segment::Cubic((0.0, 0.0), (0.25, 0.25), (0.5, 0.5), (1.0, 1.0));
+        assert_eq!(flatten(&[curve], 1e-3), [((0.0, 0.0), (1.0, 1.0))]);
+    }
+
+    #[test]
+    fn lines_follow_the_curve_within_tolerance() {
+        for tolerance in [1e-2, 1e-3, 1e-4] {
+            let lines = flatten(&[ARC], tolerance);
+            assert_eq!(lines[0].0, ARC.start());
+            assert_eq!(lines[lines.len() - 1].1, ARC.end());
+            for pair in lines.windows(2) {
+                assert_eq!(pair[0].1, pair[1].0);
+            }
+            // every point on the curve is close to some line, checked in between the line ends
+            for idx in 0..=100 {
+                let point = ARC.point_at(idx as f32 / 100.0);
+                let closest = lines
+                    .iter()
+                    .map(|(start, end)| distance_to_chord(point, *start, *end))
+                    .fold(f32::INFINITY, f32::min);
+                assert!(closest <= tolerance * 1.01, "{closest} > {tolerance}");
+            }
+        }
+    }
+
+    #[test]
+    fn tighter_tolerance_means_more_lines() {
+        let counts: Vec<usize> = [1e-2, 1e-3, 1e-4, 1e-5].into_iter().map(|tolerance| flatten(&[ARC], tolerance).len()).collect();
+        assert!(counts.windows(2).all(|pair| pair[0] < pair[1]), "{counts:?}");
+        // the deviation shrinks with the square of the line length
+        assert!(counts[3] < 16 * counts[1], "{counts:?}");
+    }
+
+    #[test]
+    fn tight_bends_get_more_lines_than_flat_parts() {
+        // nearly straight at the start, turning sharply at the end
+        let curve = Segment::Cubic((0.0, 0.0), (0.9, 0.0), (1.0, 0.0), (1.0, 0.1));
+        let lines = flatten(&[curve], 1e-4);
+        let lengths: Vec<f32> = lines.iter().map(|(start, end)| distance(*start, *end)).collect();
+        let short = lengths.iter().copied().fold(f32::INFINITY, f32::min);
+        let long = lengths.iter().copied().fold(0.0, f32::max);
+        assert!(long > 4.0 * short, "{short} {long}");
+    }
+
+    #[test]
+    fn subdivision_is_bounded() {
+        // with no tolerance only rounding ends the halving early
+        let count = flatten(&[ARC], 0.0).len();
+        assert!(count > 1000 && count <= 1 << MAX_DEPTH, "{count}");
+    }
+
+    #[test]
+    fn control_points_become_lines_and_cubics() {
+        assert!(Segment::from_control_points(&[(0.0, 0.0)]).is_empty());
+        assert_eq!(Segment::from_control_points(&[(0.0, 0.0), (1.0, 1.0)]), [Segment::Line((0.0, 0.0), (1.0, 1.0))]);
+
+        // raising the degree keeps the quadratic exactly
+        let quadratic = [(0.0, 0.0), (0.5, 1.0), (1.0, 0.0)];
+        let [cubic] = Segment::from_control_points(&quadratic)[..] else {
+            panic!("expected a single cubic");
+        };
+        let mut scratch = quadratic;
+        for t in [0.1, 0.5, 0.8] {
+            assert!(distance(cubic.point_at(t), de_casteljau(&quadratic, t, &mut scratch)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn higher_degrees_are_approximated_by_cubics() {
+        let points = [(0.0, 0.0), (0.2, 1.0), (0.4, -1.0), (0.6, 1.0), (0.8, -1.0), (1.0, 0.0)];
+        let segments = Segment::from_control_points(&points);
+        assert!(segments.len() > 1);
+        assert_eq!(segments[0].start(), points[0]);
+        assert_eq!(segments[segments.len() - 1].end(), points[5]);
+        let mut scratch = points;
+        let lines = flatten(&segments, 1e-5);
+        for idx in 0..=50 {
+            let point = de_casteljau(&points, idx as f32 / 50.0, &mut scratch);
+            let closest = lines
+                .iter()
+                .map(|(start, end)| distance_to_chord(point, *start, *end))
+                .fold(f32::INFINITY, f32::min);
+            assert!(closest <= CUBIC_TOLERANCE + 1e-4, "{closest}");
+        }
+    }
+
+    #[test]
+    fn bounds_include_where_curves_turn() {
+        let curve = Segment::Cubic((0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0));
+        let (min_x, min_y, max_x, max_y) = curve.bounds();
+        assert_eq!((min_x, min_y, max_x), (0.0, 0.0, 1.0));
+        assert!((max_y - 0.75).abs() < 1e-6, "{max_y}");
+        assert_eq!(ARC.bounds(), (0.0, 0.0, 1.0, 1.0));
+    }
+}
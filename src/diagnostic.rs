@@ -0,0 +1,59 @@
+use std::error::Error;
+use std::fmt;
+use crate::lexer::{LexError, Span, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Lex(LexError),
+    /// `found` is `None` at the end of input.
+    UnexpectedToken { expected: &'static str, found: Option<Token> },
+    UndefinedName(String),
+    /// `previous` points at the earlier definition of the name.
+    DuplicateName { name: String, previous: Span },
+    /// The names along the cycle, starting and ending with the same one.
+    DependencyCycle(Vec<String>),
+    /// `? := a => b` inside `( ... )`, standalone bounds only apply to a whole definition.
+    NestedStandaloneBounds,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: ErrorKind,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(kind: ErrorKind, span: Span) -> Self {
+        Diagnostic { kind, span }
+    }
+
+    pub fn line(&self) -> usize {
+        self.span.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.span.column
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Lex(error) => write!(f, "{error}"),
+            ErrorKind::UnexpectedToken { expected, found: Some(found) } => write!(f, "expected {expected}, found {found}"),
+            ErrorKind::UnexpectedToken { expected, found: None } => write!(f, "expected {expected}, found end of input"),
+            ErrorKind::UndefinedName(name) => write!(f, "undefined name `{name}`"),
+            ErrorKind::DuplicateName { name, previous } => write!(f, "`{name}` is already defined at {}:{}", previous.line, previous.column),
+            ErrorKind::DependencyCycle(names) => write!(f, "dependency cycle `{}`", names.join("` -> `")),
+            ErrorKind::NestedStandaloneBounds => write!(f, "standalone bounds only apply to a whole definition"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.kind)
+    }
+}
+
+impl Error for Diagnostic {}
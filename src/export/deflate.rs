@@ -0,0 +1,222 @@
+//! Just enough of zlib (RFC 1950) and deflate (RFC 1951) for PNG: one block with the fixed
+//! Huffman codes, and back references found through a hash of the next three bytes.
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Limits how many earlier occurrences of the same three bytes are tried per position.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Compresses `data` into a zlib stream.
+pub(super) fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // 32K window, default compression level, no dictionary
+    writer.bytes.extend([0x78, 0x9C]);
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let mut idx = 0;
+    while idx < data.len() {
+        let (length, distance) = longest_match(data, idx, &head, &previous);
+        if length >= MIN_MATCH {
+            writer.length(length);
+            writer.distance(distance);
+        } else {
+            writer.literal(data[idx] as u16);
+        }
+        for position in idx..idx + length.max(1) {
+            if position + MIN_MATCH <= data.len() {
+                let hash = hash(&data[position..]);
+                previous[position] = head[hash];
+                head[hash] = position;
+            }
+        }
+        idx += length.max(1);
+    }
+    writer.literal(256);
+
+    writer.flush();
+    let mut result = writer.bytes;
+    result.extend(adler32(data).to_be_bytes());
+    result
+}
+
+/// The longest earlier repetition of the bytes at `idx`, as its length and distance.
+fn longest_match(data: &[u8], idx: usize, head: &[usize], previous: &[usize]) -> (usize, usize) {
+    if idx + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+    let limit = MAX_MATCH.min(data.len() - idx);
+    let (mut best_length, mut best_distance) = (0, 0);
+    let mut candidate = head[hash(&data[idx..])];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || idx - candidate > WINDOW {
+            break;
+        }
+        let length = data[candidate..].iter().zip(&data[idx..idx + limit]).take_while(|(a, b)| a == b).count();
+        if length > best_length {
+            (best_length, best_distance) = (length, idx - candidate);
+            if length == limit {
+                break;
+            }
+        }
+        candidate = previous[candidate];
+    }
+    (best_length, best_distance)
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | bytes[2] as u32;
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Writes bits starting with the least significant bit of each byte.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored starting with their most significant bit.
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    /// A literal byte or the end of block (256), with the fixed literal/length code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let idx = LENGTH_BASE.iter().rposition(|base| *base as usize <= length).unwrap();
+        self.literal(257 + idx as u16);
+        self.bits((length - LENGTH_BASE[idx] as usize) as u32, LENGTH_EXTRA[idx] as u32);
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let idx = DISTANCE_BASE.iter().rposition(|base| *base as usize <= distance).unwrap();
+        self.code(idx as u32, 5);
+        self.bits((distance - DISTANCE_BASE[idx] as usize) as u32, DISTANCE_EXTRA[idx] as u32);
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    /// Deterministic bytes that don't compress, from a xorshift generator.
+    fn noise(len: usize, mut state: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = zlib(data);
+        assert_eq!(decompress_to_vec_zlib(&compressed).unwrap(), data);
+        compressed
+    }
+
+    #[test]
+    fn empty_input() {
+        // header, a final fixed block holding only the end of block code, adler32 of nothing
+        assert_eq!(round_trip(&[]), [0x78, 0x9C, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn runs_longer_than_the_longest_match() {
+        let data = vec![b'a'; 1000];
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < 30, "{} bytes", compressed.len());
+
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(20);
+        assert!(text.len() > MAX_MATCH);
+        round_trip(&text);
+    }
+
+    #[test]
+    fn input_longer_than_the_window() {
+        let block = noise(20_000, 1);
+        let mut data = block.clone();
+        data.extend(noise(20_000, 2));
+        // only reachable beyond the window, so it has to be written as literals again
+        data.extend(&block);
+        data.extend(&block[..5000]);
+        assert!(data.len() > WINDOW);
+        round_trip(&data);
+    }
+
+    #[test]
+    fn incompressible_input() {
+        round_trip(&noise(1000, 3));
+        round_trip(&[0, 255, 128]);
+    }
+
+    #[test]
+    fn adler32_test_vectors() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"a"), 0x0062_0062);
+        assert_eq!(adler32(b"abc"), 0x024D_0127);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        // long enough for the sums to be reduced in between
+        assert_eq!(adler32(&[0xFF; 10_000]), 0xB623_EB2B);
+    }
+}
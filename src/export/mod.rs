@@ -0,0 +1,16 @@
+mod deflate;
+mod names;
+mod png;
+mod site;
+mod specimen;
+mod svg;
+mod ttf;
+
+pub use names::FileNames;
+pub use png::{glyph_png, png, PngOptions};
+pub use site::site;
+pub use specimen::{specimen_png, specimen_svg, SpecimenOptions};
+pub use svg::{glyph_svg, sprite_sheet, SvgOptions};
+pub use ttf::{font, FontError, FontGlyph, FontOptions};
+pub use crate::outline::{LineCap, LineJoin};
+pub use crate::raster::Color;
@@ -0,0 +1,87 @@
+use std::collections::HashSet;
+
+/// Hands out file names and element ids for characters. Names may contain anything when
+/// quoted, so everything but letters, digits, `-` and `_` is replaced by `_`, the empty name
+/// becomes `_`, and names that end up the same as an earlier one, ignoring case, get `-2`,
+/// `-3`, ... appended.
+#[derive(Debug, Clone, Default)]
+pub struct FileNames {
+    /// Lowercase, so that names differing only in case don't clash on case-insensitive file
+    /// systems.
+    taken: HashSet<String>,
+}
+
+impl FileNames {
+    pub fn new() -> Self {
+        FileNames::default()
+    }
+
+    /// Keeps `name`, e.g. `index.html`, from being handed out.
+    pub fn reserve(&mut self, name: &str) {
+        self.taken.insert(name.to_lowercase());
+    }
+
+    /// A file name for the character `name`, ending in `.extension`.
+    pub fn file_name(&mut self, name: &str, extension: &str) -> String {
+        self.unique(name, &format!(".{extension}"))
+    }
+
+    /// An id for the character `name`, distinct from the others handed out.
+    pub fn id(&mut self, name: &str) -> String {
+        self.unique(name, "")
+    }
+
+    fn unique(&mut self, name: &str, suffix: &str) -> String {
+        let mut base: String = name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        // `""` would otherwise become a hidden file like `.svg`
+        if base.is_empty() {
+            base.push('_');
+        }
+        let mut result = format!("{base}{suffix}");
+        let mut counter = 2;
+        while !self.taken.insert(result.to_lowercase()) {
+            result = format!("{base}-{counter}{suffix}");
+            counter += 1;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collisions_ignore_case() {
+        let mut names = FileNames::new();
+        assert_eq!(names.file_name("a", "svg"), "a.svg");
+        assert_eq!(names.file_name("A", "svg"), "A-2.svg");
+        assert_eq!(names.file_name("a-2", "svg"), "a-2-2.svg");
+        assert_eq!(names.file_name("a", "svg"), "a-3.svg");
+        // the extension is part of the name, ids don't clash with file names
+        assert_eq!(names.file_name("a", "png"), "a.png");
+        assert_eq!(names.id("a"), "a");
+    }
+
+    #[test]
+    fn names_are_sanitised() {
+        let mut names = FileNames::new();
+        assert_eq!(names.file_name("a b", "svg"), "a_b.svg");
+        assert_eq!(names.file_name("a_b", "svg"), "a_b-2.svg");
+        assert_eq!(names.file_name("../x", "svg"), "___x.svg");
+        assert_eq!(names.file_name("日本", "svg"), "日本.svg");
+        assert_eq!(names.file_name("", "svg"), "_.svg");
+        assert_eq!(names.file_name("_", "svg"), "_-2.svg");
+        assert_eq!(names.id(""), "_");
+    }
+
+    #[test]
+    fn reserved_names_are_skipped() {
+        let mut names = FileNames::new();
+        names.reserve("index.html");
+        assert_eq!(names.file_name("Index", "html"), "Index-2.html");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::compiler::Path;
+use crate::export::deflate::zlib;
+use crate::outline::Pen;
+use crate::raster::{Color, Pixmap};
+
+/// Glyphs are drawn in their unit square (`0 0 => 1 1`), the pen and the margin are relative to it.
+#[derive(Debug, Clone)]
+pub struct PngOptions {
+    pub pen: Pen,
+    /// Width and height of the image in pixels.
+    pub size: usize,
+    /// Room left around the unit square on every side, within `size`, which shrinks the glyph
+    /// accordingly.
+    pub margin: f32,
+    pub background: Color,
+    pub foreground: Color,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        PngOptions {
+            pen: Pen::default(),
+            size: 256,
+            margin: 0.0,
+            background: Color::WHITE,
+            foreground: Color::BLACK,
+        }
+    }
+}
+
+/// Anti-aliased PNG image of a single glyph.
+pub fn glyph_png(paths: &[Path], options: &PngOptions) -> Vec<u8> {
+    let mut pixmap = Pixmap::new(options.size, options.size, options.background);
+    let scale = options.size as f32 / (1.0 + 2.0 * options.margin);
+    pixmap.draw(paths, &options.pen, (options.margin * scale, options.margin * scale), scale, options.foreground);
+    png(&pixmap)
+}
+
+/// Encodes `pixmap` as an 8 bit RGBA PNG.
+pub fn png(pixmap: &Pixmap) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend((pixmap.width as u32).to_be_bytes());
+    header.extend((pixmap.height as u32).to_be_bytes());
+    // bit depth, colour type RGBA, compression, filter and interlace method
+    header.extend([8, 6, 0, 0, 0]);
+
+    // every row starts with its filter type, none
+    let mut rows = Vec::with_capacity((pixmap.width * 4 + 1) * pixmap.height);
+    for row in pixmap.data.chunks((pixmap.width * 4).max(1)) {
+        rows.push(0);
+        rows.extend(row);
+    }
+
+    let mut result = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    chunk(&mut result, b"IHDR", &header);
+    chunk(&mut result, b"IDAT", &zlib(&rows));
+    chunk(&mut result, b"IEND", &[]);
+    result
+}
+
+fn chunk(result: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    result.extend((data.len() as u32).to_be_bytes());
+    let start = result.len();
+    result.extend(kind);
+    result.extend(data);
+    let crc = crc32(&result[start..]);
+    result.extend(crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    /// The chunks of a PNG file as type and data, checking the signature and every CRC.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(png[..8], [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        let mut result = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&rest[4..8 + length]));
+            result.push((kind.try_into().unwrap(), data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        result
+    }
+
+    #[test]
+    fn crc32_test_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn encodes_rows_with_filter_bytes() {
+        let mut pixmap = Pixmap::new(2, 2, Color::WHITE);
+        pixmap.data[4..8].copy_from_slice(&[1, 2, 3, 4]);
+        let chunks = chunks(&png(&pixmap));
+
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+        assert_eq!(
+            decompress_to_vec_zlib(&chunks[1].1).unwrap(),
+            [0, 255, 255, 255, 255, 1, 2, 3, 4, 0, 255, 255, 255, 255, 255, 255, 255, 255],
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::compiler::{Glyph, GlyphSet};
+use crate::export::names::FileNames;
+use crate::export::svg::{escape, glyph_svg, SvgOptions};
+use crate::parser::SyntaxTree;
+
+/// Size of the previews on the index page, in pixels.
+const THUMBNAIL: f32 = 64.0;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; padding: 0 1em; color: #222; }
+a { color: #15c; text-decoration: none; }
+a:hover { text-decoration: underline; }
+.radical { color: #a50; font-weight: bold; }
+.aliases, .operator { color: #777; }
+.glyphs { display: flex; flex-wrap: wrap; gap: 1em; list-style: none; padding: 0; }
+.glyphs li { width: 6em; text-align: center; overflow-wrap: anywhere; }
+.glyphs svg, .rendering svg { border: 1px solid #ddd; }
+.input { list-style: none; padding: 0; }
+.input li { display: inline-block; margin-right: 0.5em; }
+pre { background: #f5f5f5; padding: 0.5em; overflow-x: auto; }
+";
+
+/// Static HTML catalogue of the glyphs in `src`: an index, a stylesheet and one page per
+/// character with its rendering, decomposition, input sequence and the characters using it
+/// as a component. Returned as file names and their contents, `glyphs` being what `src`
+/// compiled into.
+pub fn site(src: &str, glyphs: &GlyphSet, options: &SvgOptions) -> Vec<(String, String)> {
+    // pages are named after the first name, any name or alias links to them
+    let mut file_names = FileNames::new();
+    file_names.reserve("index.html");
+    file_names.reserve("style.css");
+    let mut pages: HashMap<&str, String> = HashMap::new();
+    for glyph in glyphs {
+        let page = file_names.file_name(glyph.name(), "html");
+        for name in &glyph.names {
+            pages.insert(name, page.clone());
+        }
+    }
+
+    let mut used_in: HashMap<&str, Vec<&Glyph>> = HashMap::new();
+    for glyph in glyphs {
+        let mut components: Vec<&str> = glyph
+            .syntax_tree
+            .references()
+            .into_iter()
+            .filter_map(|(name, _)| glyphs.get(name).map(Glyph::name))
+            .collect();
+        components.sort_unstable();
+        components.dedup();
+        for component in components {
+            used_in.entry(component).or_default().push(glyph);
+        }
+    }
+
+    let mut result = vec![
+        (String::from("style.css"), String::from(STYLE)),
+        (String::from("index.html"), index(glyphs, &pages, options)),
+    ];
+    for glyph in glyphs {
+        let users = used_in.get(glyph.name()).map(Vec::as_slice).unwrap_or_default();
+        let content = glyph_page(src, glyph, users, &pages, options);
+        result.push((pages[glyph.name()].clone(), content));
+    }
+    result
+}
+
+fn index(glyphs: &GlyphSet, pages: &HashMap<&str, String>, options: &SvgOptions) -> String {
+    let thumbnail = SvgOptions { size: THUMBNAIL, grid: None, bounds: false, ..options.clone() };
+    let mut result = header("Characters");
+    writeln!(result, "<h1>Characters</h1>\n<p>{} characters, in definition order.</p>\n<ul class=\"glyphs\">", glyphs.len()).unwrap();
+    for glyph in glyphs {
+        writeln!(
+            result,
+            "<li><a href=\"{}\">{}<br>{}</a></li>",
+            escape(&pages[glyph.name()]),
+            glyph_svg(&glyph.paths, &thumbnail).trim_end(),
+            title(glyph),
+        ).unwrap();
+    }
+    result.push_str("</ul>\n</body>\n</html>\n");
+    result
+}
+
+fn glyph_page(
+    src: &str,
+    glyph: &Glyph,
+    used_in: &[&Glyph],
+    pages: &HashMap<&str, String>,
+    options: &SvgOptions,
+) -> String {
+    let mut result = header(glyph.name());
+    writeln!(result, "<p><a href=\"index.html\">All characters</a></p>\n<h1>{}</h1>", title(glyph)).unwrap();
+    if !glyph.aliases().is_empty() {
+        writeln!(result, "<p class=\"aliases\">Also known as {}</p>", escape(&glyph.aliases().join(", "))).unwrap();
+    }
+    writeln!(result, "<div class=\"rendering\">{}</div>", glyph_svg(&glyph.paths, options).trim_end()).unwrap();
+
+    result.push_str("<h2>Input</h2>\n<ol class=\"input\">\n");
+    for input in &glyph.input {
+        writeln!(result, "<li>{}</li>", link(input, pages)).unwrap();
+    }
+    result.push_str("</ol>\n");
+
+    result.push_str("<h2>Decomposition</h2>\n<ul>\n");
+    write_tree(&mut result, &glyph.syntax_tree, src, pages);
+    result.push_str("</ul>\n");
+
+    result.push_str("<h2>Used in</h2>\n");
+    if used_in.is_empty() {
+        result.push_str("<p>No other character uses this one.</p>\n");
+    } else {
+        result.push_str("<ul>\n");
+        for user in used_in {
+            writeln!(result, "<li>{}</li>", link(user.name(), pages)).unwrap();
+        }
+        result.push_str("</ul>\n");
+    }
+
+    writeln!(result, "<h2>Source</h2>\n<pre>{}</pre>\n</body>\n</html>", escape(glyph.span.text(src))).unwrap();
+    result
+}
+
+/// One list item per node, operators with their operands nested below them. The shrink
+/// wrapper the parser puts around every operand of `|` and `/` is left out unless it shrinks.
+fn write_tree(result: &mut String, tree: &SyntaxTree, src: &str, pages: &HashMap<&str, String>) {
+    let (label, children): (String, Vec<&SyntaxTree>) = match tree {
+        SyntaxTree::Shrink(0, tree, _) => return write_tree(result, tree, src, pages),
+        SyntaxTree::Ident(name, _) => (link(name, pages), Vec::new()),
+        SyntaxTree::Lines(_, span) => (format!("stroke <code>{}</code>", escape(span.text(src))), Vec::new()),
+        SyntaxTree::Bounds(_, _, span) => (format!("space <code>{}</code>", escape(span.text(src))), Vec::new()),
+        SyntaxTree::Plus(trees, _) => (operator("+", "on top of each other"), trees.iter().collect()),
+        SyntaxTree::Horizontal(trees, _) => (operator("|", "side by side"), trees.iter().collect()),
+        SyntaxTree::Vertical(trees, _) => (operator("/", "stacked"), trees.iter().collect()),
+        SyntaxTree::Inner(outer, inner, _) => (operator("{ }", "enclosing"), vec![outer.as_ref(), inner.as_ref()]),
+        SyntaxTree::Shrink(shrinkage, tree, _) => (operator(&"&".repeat(*shrinkage), "shrunk"), vec![tree.as_ref()]),
+        SyntaxTree::Square(tree, _) => (operator("[[ ]]", "squared"), vec![tree.as_ref()]),
+        SyntaxTree::HalfwaySquare(tree, _) => (operator("[ ]", "halfway squared"), vec![tree.as_ref()]),
+        SyntaxTree::Annotated(tree, _, span) => {
+            let annotations = &src[tree.span().end..span.end];
+            (format!("annotated <code>{}</code>", escape(annotations.trim())), vec![tree.as_ref()])
+        },
+    };
+    write!(result, "<li>{label}").unwrap();
+    if !children.is_empty() {
+        result.push_str("\n<ul>\n");
+        for child in children {
+            write_tree(result, child, src, pages);
+        }
+        result.push_str("</ul>\n");
+    }
+    result.push_str("</li>\n");
+}
+
+fn header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"style.css\">\n</head>\n<body>\n",
+        escape(title),
+    )
+}
+
+/// The name, with a `§` in front of radicals.
+fn title(glyph: &Glyph) -> String {
+    match glyph.is_radical {
+        true => format!("<span class=\"radical\">§</span> {}", escape(glyph.name())),
+        false => escape(glyph.name()),
+    }
+}
+
+fn operator(symbol: &str, description: &str) -> String {
+    format!("<code>{}</code> <span class=\"operator\">{description}</span>", escape(symbol))
+}
+
+/// Links to the page of `name`, names without a page are only printed.
+fn link(name: &str, pages: &HashMap<&str, String>) -> String {
+    match pages.get(name) {
+        Some(page) => format!("<a href=\"{}\">{}</a>", escape(page), escape(name)),
+        None => escape(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile_glyphs;
+
+    fn build(src: &str) -> Vec<(String, String)> {
+        site(src, &compile_glyphs(src).unwrap(), &SvgOptions::default())
+    }
+
+    fn page<'a>(files: &'a [(String, String)], name: &str) -> &'a str {
+        &files.iter().find(|(file, _)| file == name).unwrap().1
+    }
+
+    #[test]
+    fn one_page_per_character() {
+        let files = build("");
+        let names: Vec<_> = files.iter().map(|(file, _)| file.as_str()).collect();
+        assert_eq!(names, ["style.css", "index.html"]);
+        assert!(page(&files, "index.html").contains("<p>0 characters, in definition order.</p>"));
+
+        let files = build("a := 0 0 -> 1 1;");
+        let names: Vec<_> = files.iter().map(|(file, _)| file.as_str()).collect();
+        assert_eq!(names, ["style.css", "index.html", "a.html"]);
+        assert!(page(&files, "index.html").contains("<li><a href=\"a.html\"><svg "));
+
+        // the reserved files are never taken by a character, other extensions are fine
+        let files = build("index := 0 0 -> 1 1;\nstyle := index | index;\nb := style / index;");
+        let names: Vec<_> = files.iter().map(|(file, _)| file.as_str()).collect();
+        assert_eq!(names, ["style.css", "index.html", "index-2.html", "style.html", "b.html"]);
+        assert_eq!(page(&files, "index.html").matches("<li>").count(), 3);
+        assert!(page(&files, "b.html").contains("<li><a href=\"style.html\">style</a></li>"));
+    }
+
+    #[test]
+    fn pages_link_components_and_users() {
+        let files = build("§ a, alias := 0 0 -> 1 1;\nb := alias | alias;\nc := a / b;");
+        let a = page(&files, "a.html");
+        assert!(a.contains("<h1><span class=\"radical\">§</span> a</h1>"));
+        assert!(a.contains("<p class=\"aliases\">Also known as alias</p>"));
+        // listed once, however often `a` appears in them
+        assert!(a.contains("<h2>Used in</h2>\n<ul>\n<li><a href=\"b.html\">b</a></li>\n<li><a href=\"c.html\">c</a></li>\n</ul>"));
+
+        let b = page(&files, "b.html");
+        assert!(b.contains("<li><a href=\"a.html\">alias</a></li>"));
+        assert!(b.contains("<h2>Used in</h2>\n<ul>\n<li><a href=\"c.html\">c</a></li>"));
+        assert!(page(&files, "c.html").contains("<p>No other character uses this one.</p>"));
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let src = "\"<a&b>\" := 0 0 -> 1 1;\nc := \"<a&b>\" | \"<a&b>\";";
+        let files = build(src);
+        let names: Vec<_> = files.iter().map(|(file, _)| file.as_str()).collect();
+        assert_eq!(names, ["style.css", "index.html", "_a_b_.html", "c.html"]);
+        assert!(page(&files, "index.html").contains("<br>&lt;a&amp;b&gt;</a></li>"));
+
+        let page_a = page(&files, "_a_b_.html");
+        assert!(page_a.contains("<title>&lt;a&amp;b&gt;</title>"));
+        assert!(page_a.contains("<h1>&lt;a&amp;b&gt;</h1>"));
+        assert!(page_a.contains("<pre>&quot;&lt;a&amp;b&gt;&quot; := 0 0 -&gt; 1 1;</pre>"));
+
+        let c = page(&files, "c.html");
+        assert!(c.contains("<a href=\"_a_b_.html\">&lt;a&amp;b&gt;</a>"));
+        assert!(!c.contains("<a&b>"));
+    }
+}
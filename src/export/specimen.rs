@@ -0,0 +1,227 @@
+use std::fmt::Write;
+use crate::compiler::{Glyph, GlyphSet};
+use crate::export::png::png;
+use crate::export::svg::{escape, number, path_data};
+use crate::outline::Pen;
+use crate::raster::{self, Color, Pixmap};
+
+/// Lines of text under each glyph: the name, the aliases and the input sequence.
+const LABELS: usize = 3;
+
+/// A grid with one cell per glyph, the glyph square on top and its labels below.
+#[derive(Debug, Clone)]
+pub struct SpecimenOptions {
+    pub pen: Pen,
+    /// Width and height of the glyph square in each cell, in pixels.
+    pub cell: usize,
+    pub columns: usize,
+    /// Space around the unit square within the glyph square, relative to the unit square.
+    pub margin: f32,
+    pub background: Color,
+    pub foreground: Color,
+}
+
+impl Default for SpecimenOptions {
+    fn default() -> Self {
+        SpecimenOptions {
+            pen: Pen::default(),
+            cell: 96,
+            columns: 8,
+            margin: 0.15,
+            background: Color::WHITE,
+            foreground: Color::BLACK,
+        }
+    }
+}
+
+/// Sizes in pixels, derived from the options.
+struct Layout {
+    columns: usize,
+    rows: usize,
+    /// Scale of the 5x7 pixel label font.
+    text_scale: usize,
+    line_height: usize,
+    padding: usize,
+    cell_height: usize,
+    /// Longest label that fits into a cell, in characters.
+    label_length: usize,
+}
+
+impl Layout {
+    fn new(count: usize, options: &SpecimenOptions) -> Self {
+        let columns = options.columns.min(count).max(1);
+        let text_scale = (options.cell / 128).max(1);
+        let line_height = 10 * text_scale;
+        let padding = 3 * text_scale;
+        Layout {
+            columns,
+            rows: count.div_ceil(columns).max(1),
+            text_scale,
+            line_height,
+            padding,
+            cell_height: options.cell + LABELS * line_height + padding,
+            label_length: options.cell.saturating_sub(2 * padding) / (6 * text_scale),
+        }
+    }
+
+    fn width(&self, options: &SpecimenOptions) -> usize {
+        self.columns * options.cell
+    }
+
+    fn height(&self) -> usize {
+        self.rows * self.cell_height
+    }
+
+    /// Top left corner of the cell of the `idx`th glyph.
+    fn origin(&self, idx: usize, options: &SpecimenOptions) -> (usize, usize) {
+        (idx % self.columns * options.cell, idx / self.columns * self.cell_height)
+    }
+}
+
+/// Contact sheet of all glyphs in definition order, as an SVG document.
+pub fn specimen_svg(glyphs: &GlyphSet, options: &SpecimenOptions) -> String {
+    let layout = Layout::new(glyphs.len(), options);
+    let (width, height) = (layout.width(options), layout.height());
+    let scale = options.cell as f32 / (1.0 + 2.0 * options.margin);
+
+    let mut result = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n",
+    );
+    if options.background.a > 0 {
+        writeln!(result, "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\"/>", options.background).unwrap();
+    }
+    writeln!(result, "<g font-family=\"monospace\" font-size=\"{}\" fill=\"{}\">", 8 * layout.text_scale, options.foreground).unwrap();
+
+    for (idx, glyph) in glyphs.iter().enumerate() {
+        let (x, y) = layout.origin(idx, options);
+        writeln!(result, "<g transform=\"translate({x} {y})\">").unwrap();
+        writeln!(
+            result,
+            "<path transform=\"translate({} {}) scale({})\" d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\" stroke-miterlimit=\"{}\"/>",
+            number(options.margin * scale),
+            number(options.margin * scale),
+            number(scale),
+            path_data(&glyph.paths),
+            options.foreground,
+            number(options.pen.width),
+            options.pen.cap.as_str(),
+            options.pen.join.as_str(),
+            number(options.pen.miter_limit),
+        ).unwrap();
+        for (line, label) in labels(glyph, layout.label_length).iter().enumerate() {
+            if label.is_empty() {
+                continue;
+            }
+            writeln!(
+                result,
+                "<text x=\"{}\" y=\"{}\"{}>{}</text>",
+                layout.padding,
+                options.cell + (line + 1) * layout.line_height,
+                if line == 0 { "" } else { " fill-opacity=\"0.6\"" },
+                escape(label),
+            ).unwrap();
+        }
+        result.push_str("</g>\n");
+    }
+    result.push_str("</g>\n</svg>\n");
+    result
+}
+
+/// Contact sheet of all glyphs in definition order, as a PNG image.
+pub fn specimen_png(glyphs: &GlyphSet, options: &SpecimenOptions) -> Vec<u8> {
+    let layout = Layout::new(glyphs.len(), options);
+    let mut pixmap = Pixmap::new(layout.width(options), layout.height(), options.background);
+    let scale = options.cell as f32 / (1.0 + 2.0 * options.margin);
+    let secondary = Color { a: (options.foreground.a as f32 * 0.6).round() as u8, ..options.foreground };
+
+    for (idx, glyph) in glyphs.iter().enumerate() {
+        let (x, y) = layout.origin(idx, options);
+        let origin = (x as f32 + options.margin * scale, y as f32 + options.margin * scale);
+        pixmap.draw(&glyph.paths, &options.pen, origin, scale, options.foreground);
+        for (line, label) in labels(glyph, layout.label_length).iter().enumerate() {
+            let (_, text_height) = raster::text_size(label, layout.text_scale);
+            let top = y + options.cell + line * layout.line_height + layout.line_height - text_height;
+            let color = if line == 0 { options.foreground } else { secondary };
+            pixmap.text(label, (x + layout.padding, top), layout.text_scale, color);
+        }
+    }
+    png(&pixmap)
+}
+
+/// The name, with `§` in front for radicals, the aliases and the input sequence, each cut
+/// short with `…` beyond `length` characters.
+fn labels(glyph: &Glyph, length: usize) -> [String; LABELS] {
+    let name = match glyph.is_radical {
+        true => format!("§ {}", glyph.name()),
+        false => glyph.name().to_string(),
+    };
+    [name, glyph.aliases().join(", "), glyph.input.join(" ")].map(|label| shortened(label, length))
+}
+
+fn shortened(label: String, length: usize) -> String {
+    if label.chars().count() <= length {
+        return label;
+    }
+    let mut result: String = label.chars().take(length.saturating_sub(1)).collect();
+    result.push('…');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile_glyphs;
+
+    /// Width and height from the `IHDR` chunk.
+    fn png_size(png: &[u8]) -> (u32, u32) {
+        (u32::from_be_bytes(png[16..20].try_into().unwrap()), u32::from_be_bytes(png[20..24].try_into().unwrap()))
+    }
+
+    #[test]
+    fn layout_for_any_number_of_glyphs() {
+        let options = SpecimenOptions::default();
+        let cell_height = 96 + LABELS * 10 + 3;
+        for (count, columns, rows) in [(0, 1, 1), (1, 1, 1), (8, 8, 1), (9, 8, 2), (17, 8, 3)] {
+            let layout = Layout::new(count, &options);
+            assert_eq!((layout.columns, layout.rows), (columns, rows), "{count} glyphs");
+            assert_eq!((layout.width(&options), layout.height()), (columns * 96, rows * cell_height));
+        }
+        let layout = Layout::new(17, &options);
+        assert_eq!(layout.origin(0, &options), (0, 0));
+        assert_eq!(layout.origin(9, &options), (96, cell_height));
+        assert_eq!(layout.origin(16, &options), (0, 2 * cell_height));
+
+        // large cells get larger text
+        let layout = Layout::new(1, &SpecimenOptions { cell: 256, ..options });
+        assert_eq!((layout.text_scale, layout.line_height, layout.label_length), (2, 20, 20));
+    }
+
+    #[test]
+    fn images_match_the_layout() {
+        let glyphs = compile_glyphs("a := 0 0 -> 1 1;\nb := a | a;\nc := a / a;").unwrap();
+        let options = SpecimenOptions { cell: 32, columns: 2, ..SpecimenOptions::default() };
+        assert_eq!(png_size(&specimen_png(&glyphs, &options)), (64, 2 * (32 + 33)));
+        assert_eq!(png_size(&specimen_png(&compile_glyphs("").unwrap(), &options)), (32, 65));
+
+        let svg = specimen_svg(&glyphs, &options);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"64\" height=\"130\""));
+        assert_eq!(svg.matches("<path ").count(), 3);
+        assert!(svg.contains("<g transform=\"translate(0 65)\">"));
+    }
+
+    #[test]
+    fn labels_are_shortened_to_fit() {
+        assert_eq!(shortened(String::from("stroke"), 6), "stroke");
+        assert_eq!(shortened(String::from("strokes"), 6), "strok…");
+        assert_eq!(shortened(String::from("日本語"), 2), "日…");
+        assert_eq!(shortened(String::from("ab"), 0), "…");
+
+        let glyphs = compile_glyphs("§ \"<long & name>\", alias := 0 0 -> 1 1;").unwrap();
+        let labels = labels(glyphs.get("alias").unwrap(), 8);
+        assert_eq!(labels, ["§ <long…", "alias", "<long &…"]);
+        // a 48 pixel cell has room for 7 characters
+        let svg = specimen_svg(&glyphs, &SpecimenOptions { cell: 48, ..SpecimenOptions::default() });
+        assert!(svg.contains(">§ &lt;lon…</text>"), "{svg}");
+        assert!(svg.contains(">&lt;long …</text>"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::Write;
+use crate::compiler::{Path, Segment};
+use crate::export::{FileNames, LineCap, LineJoin};
+
+/// Glyphs are drawn in their unit square (`0 0 => 1 1`), all lengths except `size` are relative to it.
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    pub stroke_width: f32,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    /// Miter joins longer than `miter_limit * stroke_width` are beveled, as with [`Pen`](crate::outline::Pen).
+    pub miter_limit: f32,
+    /// Width and height of the canvas in pixels.
+    pub size: f32,
+    /// Added to the view box on every side, so strokes and standalone bounds reaching past the
+    /// unit square aren't cut off.
+    pub margin: f32,
+    /// Number of cells per side of a helper grid.
+    pub grid: Option<usize>,
+    /// Outline the unit square and the bounding box of the strokes.
+    pub bounds: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            stroke_width: 0.05,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            miter_limit: 4.0,
+            size: 256.0,
+            margin: 0.0,
+            grid: None,
+            bounds: false,
+        }
+    }
+}
+
+/// Standalone SVG document for a single glyph.
+pub fn glyph_svg(paths: &[Path], options: &SvgOptions) -> String {
+    let mut result = header(options);
+    write_glyph(&mut result, paths, options);
+    result.push_str("</svg>\n");
+    result
+}
+
+/// Single SVG with one `<symbol id="name">` per glyph, to be used with `<use href="#name"/>`.
+/// Ids are handed out by [`FileNames::id`].
+pub fn sprite_sheet<'a>(glyphs: impl IntoIterator<Item = (&'a str, &'a [Path])>, options: &SvgOptions) -> String {
+    let mut ids = FileNames::new();
+    let mut result = String::from("<svg xmlns=\"http://www.w3.org/2000/svg\" style=\"display: none\">\n");
+    for (name, paths) in glyphs {
+        writeln!(result, "<symbol id=\"{}\" viewBox=\"{}\">", ids.id(name), view_box(options)).unwrap();
+        write_glyph(&mut result, paths, options);
+        result.push_str("</symbol>\n");
+    }
+    result.push_str("</svg>\n");
+    result
+}
+
+fn header(options: &SvgOptions) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{}\">\n",
+        number(options.size),
+        number(options.size),
+        view_box(options),
+    )
+}
+
+fn view_box(options: &SvgOptions) -> String {
+    let margin = options.margin;
+    format!("{} {} {} {}", number(-margin), number(-margin), number(1.0 + 2.0 * margin), number(1.0 + 2.0 * margin))
+}
+
+fn write_glyph(result: &mut String, paths: &[Path], options: &SvgOptions) {
+    if let Some(cells) = options.grid {
+        write_grid(result, cells);
+    }
+    if options.bounds {
+        write_bounds(result, paths);
+    }
+    writeln!(
+        result,
+        "<path d=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\" stroke-linecap=\"{}\" stroke-linejoin=\"{}\" stroke-miterlimit=\"{}\"/>",
+        path_data(paths),
+        number(options.stroke_width),
+        options.line_cap.as_str(),
+        options.line_join.as_str(),
+        number(options.miter_limit),
+    ).unwrap();
+}
+
+fn write_grid(result: &mut String, cells: usize) {
+    let mut d = String::new();
+    for idx in 0..=cells {
+        let at = number(idx as f32 / cells as f32);
+        write!(d, "M{at} 0V1M0 {at}H1").unwrap();
+    }
+    writeln!(result, "<path d=\"{d}\" fill=\"none\" stroke=\"#ccc\" stroke-width=\"0.005\"/>").unwrap();
+}
+
+fn write_bounds(result: &mut String, paths: &[Path]) {
+    result.push_str("<rect x=\"0\" y=\"0\" width=\"1\" height=\"1\" fill=\"none\" stroke=\"#69f\" stroke-width=\"0.005\" stroke-dasharray=\"0.02\"/>\n");
+    let Some((min_x, min_y, max_x, max_y)) = paths.iter().filter_map(Path::bounds).reduce(|a, b| {
+        (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+    }) else {
+        return;
+    };
+    writeln!(
+        result,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#f96\" stroke-width=\"0.005\"/>",
+        number(min_x),
+        number(min_y),
+        number(max_x - min_x),
+        number(max_y - min_y),
+    ).unwrap();
+}
+
+/// Every path is one subpath, so its segments get joins instead of caps.
+pub(super) fn path_data(paths: &[Path]) -> String {
+    let mut result = String::new();
+    for path in paths {
+        let Some(from) = path.start() else {
+            continue;
+        };
+        write!(result, "M{} {}", number(from.0), number(from.1)).unwrap();
+        for segment in &path.segments {
+            write_segment(&mut result, segment);
+        }
+        if path.is_closed() {
+            result.push('Z');
+        }
+    }
+    result
+}
+
+fn write_segment(result: &mut String, segment: &Segment) {
+    match segment {
+        Segment::Line(_, to) => write!(result, "L{} {}", number(to.0), number(to.1)).unwrap(),
+        Segment::Cubic(_, c1, c2, to) => write!(
+            result,
+            "C{} {} {} {} {} {}",
+            number(c1.0),
+            number(c1.1),
+            number(c2.0),
+            number(c2.1),
+            number(to.0),
+            number(to.1),
+        ).unwrap(),
+    }
+}
+
+/// Rounded to 4 decimals without trailing zeros, to keep the files small.
+pub(super) fn number(value: f32) -> String {
+    let result = format!("{value:.4}");
+    let result = result.trim_end_matches('0').trim_end_matches('.');
+    match result {
+        "-0" | "" => String::from("0"),
+        result => result.to_string(),
+    }
+}
+
+pub(super) fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(segments: Vec<Segment>) -> Path {
+        Path { segments }
+    }
+
+    #[test]
+    fn path_data_keeps_paths_and_curves() {
+        let paths = [
+            path(vec![Segment::Line((0.1, 0.1), (0.1, 0.9)), Segment::Line((0.1, 0.9), (0.9, 0.9))]),
+            path(vec![]),
+            path(vec![Segment::Cubic((0.0, 0.5), (0.25, 0.0), (0.75, 1.0), (1.0, 0.5))]),
+            path(vec![Segment::Line((0.2, 0.2), (0.8, 0.2)), Segment::Line((0.8, 0.2), (0.5, 0.8)), Segment::Line((0.5, 0.8), (0.2, 0.2))]),
+        ];
+        assert_eq!(path_data(&paths), "M0.1 0.1L0.1 0.9L0.9 0.9M0 0.5C0.25 0 0.75 1 1 0.5M0.2 0.2L0.8 0.2L0.5 0.8L0.2 0.2Z");
+    }
+
+    #[test]
+    fn numbers_are_short() {
+        assert_eq!(number(1.0), "1");
+        assert_eq!(number(0.25), "0.25");
+        assert_eq!(number(1.0 / 3.0), "0.3333");
+        assert_eq!(number(-0.00001), "0");
+        assert_eq!(number(-1.5), "-1.5");
+    }
+
+    #[test]
+    fn glyph_documents() {
+        let paths = [path(vec![Segment::Line((0.0, 0.0), (1.0, 1.0))])];
+        let options = SvgOptions { margin: 0.25, line_join: LineJoin::Miter, miter_limit: 2.0, ..SvgOptions::default() };
+        assert_eq!(glyph_svg(&paths, &options), "\
+<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"256\" height=\"256\" viewBox=\"-0.25 -0.25 1.5 1.5\">
+<path d=\"M0 0L1 1\" fill=\"none\" stroke=\"black\" stroke-width=\"0.05\" stroke-linecap=\"round\" stroke-linejoin=\"miter\" stroke-miterlimit=\"2\"/>
+</svg>
+");
+    }
+
+    #[test]
+    fn sprite_ids_are_unique() {
+        let paths = [path(vec![Segment::Line((0.0, 0.0), (1.0, 1.0))])];
+        let sheet = sprite_sheet([("a", &paths[..]), ("A", &paths[..]), ("a b", &paths[..])], &SvgOptions::default());
+        let ids: Vec<_> = sheet.split("<symbol id=\"").skip(1).map(|it| &it[..it.find('"').unwrap()]).collect();
+        assert_eq!(ids, ["a", "A-2", "a_b"]);
+        assert_eq!(sheet.matches("viewBox=\"0 0 1 1\"").count(), 3);
+    }
+}
@@ -0,0 +1,743 @@
+use std::fmt;
+use crate::compiler::Path;
+use crate::outline::{self, Contour, Pen};
+
+#[derive(Debug, Clone)]
+pub struct FontOptions {
+    pub family: String,
+    pub units_per_em: u16,
+    /// The pen the centre lines are stroked with.
+    pub pen: Pen,
+    /// Largest distance between a curve and the straight lines the outline follows instead,
+    /// relative to the glyph square.
+    pub tolerance: f32,
+    /// Advance width relative to the glyph square.
+    pub advance: f32,
+    /// Distance of the baseline from the top of the glyph square, relative to it.
+    pub baseline: f32,
+}
+
+impl Default for FontOptions {
+    fn default() -> Self {
+        FontOptions {
+            family: String::from("vglyph"),
+            units_per_em: 1000,
+            pen: Pen::default(),
+            tolerance: 0.001,
+            advance: 1.0,
+            baseline: 0.8,
+        }
+    }
+}
+
+pub struct FontGlyph<'a> {
+    pub codepoint: u32,
+    pub paths: &'a [Path],
+}
+
+/// Why a font can't be built. Glyphs are named by their codepoint, `None` is `.notdef`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FontError {
+    /// Outside of 16..=16384, the range TrueType allows.
+    UnitsPerEm(u16),
+    /// The option with this name doesn't fit in 16 bits once scaled to font units.
+    MetricOutOfRange(&'static str),
+    /// A point of the glyph, or the step to it from the previous one, doesn't fit in 16 bits.
+    CoordinateOutOfRange(Option<u32>),
+    /// The glyph has more points than a TrueType outline can hold.
+    TooManyPoints(Option<u32>),
+    /// More glyphs, or runs of consecutive codepoints, than the font tables can hold.
+    TooManyGlyphs,
+    /// Two glyphs are mapped to this codepoint.
+    DuplicateCodepoint(u32),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let glyph = |codepoint: &Option<u32>| match codepoint {
+            Some(codepoint) => format!("the glyph for U+{codepoint:04X}"),
+            None => String::from("the .notdef glyph"),
+        };
+        match self {
+            FontError::UnitsPerEm(units) => write!(f, "{units} units per em is outside of 16 to 16384"),
+            FontError::MetricOutOfRange(name) => write!(f, "the {name} is too large for the units per em"),
+            FontError::CoordinateOutOfRange(codepoint) => write!(f, "{} reaches too far for the units per em", glyph(codepoint)),
+            FontError::TooManyPoints(codepoint) => write!(f, "{} has more than {} points", glyph(codepoint), u16::MAX),
+            FontError::TooManyGlyphs => write!(f, "too many glyphs for one font"),
+            FontError::DuplicateCodepoint(codepoint) => write!(f, "more than one glyph for U+{codepoint:04X}"),
+        }
+    }
+}
+
+/// A glyph in font units, as stored in `glyf`.
+struct Outline {
+    /// Index of the last point of each contour.
+    end_points: Vec<u16>,
+    /// Each point as the step from the previous one, the first one from the origin.
+    deltas: Vec<(i16, i16)>,
+    /// `(x_min, y_min, x_max, y_max)`, all zero for empty glyphs.
+    bounds: (i16, i16, i16, i16),
+}
+
+/// Lengths relative to the glyph square in font units, which are 16 bits wide.
+#[derive(Clone, Copy)]
+struct Scale(f32);
+
+impl Scale {
+    fn units(self, value: f32) -> Option<i16> {
+        let result = (value * self.0).round();
+        (result >= i16::MIN as f32 && result <= i16::MAX as f32).then_some(result as i16)
+    }
+
+    /// Like [`units`](Scale::units), naming the option that is out of range.
+    fn metric(self, value: f32, name: &'static str) -> Result<i16, FontError> {
+        self.units(value).ok_or(FontError::MetricOutOfRange(name))
+    }
+}
+
+/// Builds a TrueType font (`.ttf`) from the stroked glyphs. Glyph 0 is `.notdef` and glyph 1 an
+/// empty space mapped to U+0020, unless one of the given glyphs is, which follow in order.
+/// Fails instead of writing a corrupt font when the glyphs or metrics don't fit in its tables.
+pub fn font(glyphs: &[FontGlyph], options: &FontOptions) -> Result<Vec<u8>, FontError> {
+    if !(16..=16384).contains(&options.units_per_em) {
+        return Err(FontError::UnitsPerEm(options.units_per_em));
+    }
+    // glyph ids are 16 bits, including .notdef and the space
+    if glyphs.len() > u16::MAX as usize - 2 {
+        return Err(FontError::TooManyGlyphs);
+    }
+    let scale = Scale(options.units_per_em as f32);
+    // checked first, .notdef would otherwise be blamed
+    let advance = u16::try_from(scale.metric(options.advance, "advance")?).map_err(|_| FontError::MetricOutOfRange("advance"))?;
+    for baseline in [options.baseline, options.baseline - 1.0] {
+        scale.metric(baseline, "baseline")?;
+    }
+    let to_font = |(x, y): (f32, f32)| Some((scale.units(x)?, scale.units(options.baseline - y)?));
+
+    let mut outlines = vec![outline(&notdef(options.advance), to_font, None)?, outline(&[], to_font, None)?];
+    for glyph in glyphs {
+        let contours = outline::stroke(glyph.paths, &options.pen, options.tolerance);
+        outlines.push(outline(&contours, to_font, Some(glyph.codepoint))?);
+    }
+
+    let mut mapping: Vec<(u32, u16)> = glyphs
+        .iter()
+        .enumerate()
+        .map(|(idx, glyph)| (glyph.codepoint, idx as u16 + 2))
+        .collect();
+    if !mapping.iter().any(|(codepoint, _)| *codepoint == 0x20) {
+        mapping.push((0x20, 1));
+    }
+    mapping.sort_by_key(|(codepoint, _)| *codepoint);
+    if let Some(pair) = mapping.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(FontError::DuplicateCodepoint(pair[0].0));
+    }
+
+    let (glyf, loca) = glyf_and_loca(&outlines);
+    let metrics = Metrics::new(&outlines, advance, scale, options)?;
+
+    let mut tables = vec![
+        (*b"OS/2", os2(&metrics, &mapping, scale, options)?),
+        (*b"cmap", cmap(&mapping)?),
+        (*b"glyf", glyf),
+        (*b"head", head(&metrics, options)),
+        (*b"hhea", hhea(&metrics, advance, outlines.len())),
+        (*b"hmtx", hmtx(&outlines, advance)),
+        (*b"loca", loca),
+        (*b"maxp", maxp(&outlines)),
+        (*b"name", name(&options.family)),
+        (*b"post", post(scale, options)?),
+    ];
+    tables.sort_by_key(|(tag, _)| *tag);
+    Ok(assemble(tables))
+}
+
+fn notdef(advance: f32) -> Vec<Contour> {
+    let (left, right) = (0.1 * advance, 0.9 * advance);
+    let outer = vec![(left, 0.0), (right, 0.0), (right, 1.0), (left, 1.0)];
+    let inner = vec![(left + 0.05, 0.05), (left + 0.05, 0.95), (right - 0.05, 0.95), (right - 0.05, 0.05)];
+    vec![outer, inner]
+}
+
+fn outline(contours: &[Contour], to_font: impl Fn((f32, f32)) -> Option<(i16, i16)>, codepoint: Option<u32>) -> Result<Outline, FontError> {
+    let mut points = Vec::new();
+    let mut end_points = Vec::new();
+    for contour in contours {
+        let mut contour: Vec<_> = contour
+            .iter()
+            .map(|point| to_font(*point))
+            .collect::<Option<_>>()
+            .ok_or(FontError::CoordinateOutOfRange(codepoint))?;
+        contour.dedup();
+        while contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+        if contour.len() < 3 {
+            continue;
+        }
+        points.extend(contour);
+        // the point count is stored in 16 bits as well, in maxp
+        if points.len() > u16::MAX as usize {
+            return Err(FontError::TooManyPoints(codepoint));
+        }
+        end_points.push(points.len() as u16 - 1);
+    }
+
+    let bounds = points
+        .iter()
+        .fold(None, |acc, (x, y)| match acc {
+            None => Some((*x, *y, *x, *y)),
+            Some((x_min, y_min, x_max, y_max)) => Some((x_min.min(*x), y_min.min(*y), x_max.max(*x), y_max.max(*y))),
+        })
+        .unwrap_or_default();
+
+    let mut previous = (0, 0);
+    let mut deltas = Vec::with_capacity(points.len());
+    for (x, y) in points {
+        let delta = (i16::try_from(i32::from(x) - i32::from(previous.0)), i16::try_from(i32::from(y) - i32::from(previous.1)));
+        match delta {
+            (Ok(dx), Ok(dy)) => deltas.push((dx, dy)),
+            _ => return Err(FontError::CoordinateOutOfRange(codepoint)),
+        }
+        previous = (x, y);
+    }
+
+    Ok(Outline { end_points, deltas, bounds })
+}
+
+struct Metrics {
+    bounds: (i16, i16, i16, i16),
+    ascender: i16,
+    descender: i16,
+    min_right_side_bearing: i16,
+}
+
+impl Metrics {
+    fn new(outlines: &[Outline], advance: u16, scale: Scale, options: &FontOptions) -> Result<Self, FontError> {
+        let non_empty: Vec<_> = outlines.iter().filter(|it| !it.end_points.is_empty()).collect();
+        let bounds = non_empty
+            .iter()
+            .map(|it| it.bounds)
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+            .unwrap_or_default();
+        let min_right_side_bearing = non_empty.iter().map(|it| advance as i32 - it.bounds.2 as i32).min().unwrap_or(0);
+        Ok(Metrics {
+            bounds,
+            ascender: bounds.3.max(scale.metric(options.baseline, "baseline")?),
+            descender: bounds.1.min(scale.metric(options.baseline - 1.0, "baseline")?),
+            min_right_side_bearing: i16::try_from(min_right_side_bearing).map_err(|_| FontError::MetricOutOfRange("advance"))?,
+        })
+    }
+}
+
+fn glyf_and_loca(outlines: &[Outline]) -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    for outline in outlines {
+        put_u32(&mut loca, glyf.len() as u32);
+        if outline.end_points.is_empty() {
+            continue;
+        }
+        // at least three points each, so far fewer contours than the 2^15 allowed
+        put_i16(&mut glyf, outline.end_points.len() as i16);
+        let (x_min, y_min, x_max, y_max) = outline.bounds;
+        [x_min, y_min, x_max, y_max].into_iter().for_each(|it| put_i16(&mut glyf, it));
+        outline.end_points.iter().for_each(|it| put_u16(&mut glyf, *it));
+        put_u16(&mut glyf, 0);
+        // every point is on the curve, with both coordinates as full 16 bit deltas
+        glyf.extend(std::iter::repeat_n(0x01, outline.deltas.len()));
+        outline.deltas.iter().for_each(|(dx, _)| put_i16(&mut glyf, *dx));
+        outline.deltas.iter().for_each(|(_, dy)| put_i16(&mut glyf, *dy));
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    put_u32(&mut loca, glyf.len() as u32);
+    (glyf, loca)
+}
+
+fn head(metrics: &Metrics, options: &FontOptions) -> Vec<u8> {
+    let mut result = Vec::new();
+    put_u16(&mut result, 1);
+    put_u16(&mut result, 0);
+    put_u32(&mut result, 0x0001_0000);
+    // checksum adjustment, filled in once the whole file is known
+    put_u32(&mut result, 0);
+    put_u32(&mut result, 0x5F0F_3CF5);
+    // baseline at y = 0, left side bearing at x = 0, integer scaling
+    put_u16(&mut result, 0b1011);
+    put_u16(&mut result, options.units_per_em);
+    result.extend([0; 16]);
+    let (x_min, y_min, x_max, y_max) = metrics.bounds;
+    [x_min, y_min, x_max, y_max].into_iter().for_each(|it| put_i16(&mut result, it));
+    put_u16(&mut result, 0);
+    put_u16(&mut result, 8);
+    put_i16(&mut result, 2);
+    // long loca offsets
+    put_i16(&mut result, 1);
+    put_i16(&mut result, 0);
+    result
+}
+
+fn hhea(metrics: &Metrics, advance: u16, glyph_count: usize) -> Vec<u8> {
+    let mut result = Vec::new();
+    put_u16(&mut result, 1);
+    put_u16(&mut result, 0);
+    put_i16(&mut result, metrics.ascender);
+    put_i16(&mut result, metrics.descender);
+    put_i16(&mut result, 0);
+    put_u16(&mut result, advance);
+    put_i16(&mut result, metrics.bounds.0);
+    put_i16(&mut result, metrics.min_right_side_bearing);
+    put_i16(&mut result, metrics.bounds.2);
+    put_i16(&mut result, 1);
+    put_i16(&mut result, 0);
+    result.extend([0; 12]);
+    put_u16(&mut result, glyph_count as u16);
+    result
+}
+
+fn hmtx(outlines: &[Outline], advance: u16) -> Vec<u8> {
+    let mut result = Vec::new();
+    for outline in outlines {
+        put_u16(&mut result, advance);
+        put_i16(&mut result, outline.bounds.0);
+    }
+    result
+}
+
+fn maxp(outlines: &[Outline]) -> Vec<u8> {
+    let metrics = (
+        outlines.iter().map(|it| it.deltas.len()).max().unwrap_or(0),
+        outlines.iter().map(|it| it.end_points.len()).max().unwrap_or(0),
+    );
+    let mut result = Vec::new();
+    put_u32(&mut result, 0x0001_0000);
+    put_u16(&mut result, outlines.len() as u16);
+    put_u16(&mut result, metrics.0 as u16);
+    put_u16(&mut result, metrics.1 as u16);
+    put_u16(&mut result, 0);
+    put_u16(&mut result, 0);
+    // zones, twilight points, storage, function defs, instruction defs, stack elements,
+    // size of instructions, component elements and component depth
+    put_u16(&mut result, 2);
+    result.extend([0; 16]);
+    result
+}
+
+fn os2(metrics: &Metrics, mapping: &[(u32, u16)], scale: Scale, options: &FontOptions) -> Result<Vec<u8>, FontError> {
+    // the fixed sizes are below one em, so only the advance and stroke width can be out of range
+    let em = |value: f32| scale.units(value).unwrap_or_default();
+    let first_char = mapping.first().map_or(0, |(codepoint, _)| (*codepoint).min(0xFFFF) as u16);
+    let last_char = mapping.last().map_or(0, |(codepoint, _)| (*codepoint).min(0xFFFF) as u16);
+
+    let mut result = Vec::new();
+    put_u16(&mut result, 4);
+    put_i16(&mut result, scale.metric(options.advance, "advance")?);
+    put_u16(&mut result, 400);
+    put_u16(&mut result, 5);
+    put_u16(&mut result, 0);
+    // subscript and superscript sizes and offsets, strikeout size and position
+    [em(0.65), em(0.6), 0, em(0.075), em(0.65), em(0.6), 0, em(0.35), scale.metric(options.pen.width, "stroke width")?, em(0.25)]
+        .into_iter()
+        .for_each(|it| put_i16(&mut result, it));
+    put_i16(&mut result, 0);
+    result.extend([0; 10]);
+    // unicode ranges: basic latin for the space, private use area for the usual glyph codepoints
+    put_u32(&mut result, 1);
+    put_u32(&mut result, 1 << 28);
+    put_u32(&mut result, 0);
+    put_u32(&mut result, 0);
+    result.extend(b"NONE");
+    // regular
+    put_u16(&mut result, 0x40);
+    put_u16(&mut result, first_char);
+    put_u16(&mut result, last_char);
+    put_i16(&mut result, metrics.ascender);
+    put_i16(&mut result, metrics.descender);
+    put_i16(&mut result, 0);
+    put_u16(&mut result, metrics.ascender.max(0).unsigned_abs());
+    put_u16(&mut result, metrics.descender.min(0).unsigned_abs());
+    // code page ranges: latin 1
+    put_u32(&mut result, 1);
+    put_u32(&mut result, 0);
+    put_i16(&mut result, metrics.ascender);
+    put_i16(&mut result, metrics.ascender);
+    put_u16(&mut result, 0);
+    put_u16(&mut result, 0x20);
+    put_u16(&mut result, 0);
+    Ok(result)
+}
+
+/// Format 4 for the basic multilingual plane and format 12 for everything.
+fn cmap(mapping: &[(u32, u16)]) -> Result<Vec<u8>, FontError> {
+    // runs of consecutive codepoints mapped to consecutive glyphs, as (first codepoint, last codepoint, first glyph)
+    let mut groups: Vec<(u32, u32, u16)> = Vec::new();
+    for &(codepoint, glyph) in mapping {
+        match groups.last_mut() {
+            Some((start, end, start_glyph)) if *end + 1 == codepoint && *start_glyph as u32 + codepoint - *start == glyph as u32 => {
+                *end = codepoint;
+            },
+            _ => groups.push((codepoint, codepoint, glyph)),
+        }
+    }
+    let format4 = cmap_format4(&groups)?;
+    let format12 = cmap_format12(&groups);
+
+    let mut result = Vec::new();
+    put_u16(&mut result, 0);
+    put_u16(&mut result, 2);
+    put_u16(&mut result, 3);
+    put_u16(&mut result, 1);
+    put_u32(&mut result, 4 + 2 * 8);
+    put_u16(&mut result, 3);
+    put_u16(&mut result, 10);
+    put_u32(&mut result, 4 + 2 * 8 + format4.len() as u32);
+    result.extend(format4);
+    result.extend(format12);
+    Ok(result)
+}
+
+fn cmap_format4(groups: &[(u32, u32, u16)]) -> Result<Vec<u8>, FontError> {
+    let mut segments: Vec<(u16, u16, u16)> = groups
+        .iter()
+        .filter(|(start, _, _)| *start < 0xFFFF)
+        .map(|&(start, end, glyph)| (start as u16, end.min(0xFFFE) as u16, glyph))
+        .collect();
+    // required last segment, mapping 0xFFFF to glyph 0
+    segments.push((0xFFFF, 0xFFFF, 0));
+    // the subtable length is 16 bits too
+    if 16 + 8 * segments.len() > u16::MAX as usize {
+        return Err(FontError::TooManyGlyphs);
+    }
+
+    let segment_count = segments.len() as u16;
+    let (search_range, entry_selector) = search_parameters(segment_count);
+    let mut result = Vec::new();
+    put_u16(&mut result, 4);
+    put_u16(&mut result, 16 + 8 * segment_count);
+    put_u16(&mut result, 0);
+    put_u16(&mut result, 2 * segment_count);
+    put_u16(&mut result, 2 * search_range);
+    put_u16(&mut result, entry_selector);
+    put_u16(&mut result, 2 * segment_count - 2 * search_range);
+    segments.iter().for_each(|(_, end, _)| put_u16(&mut result, *end));
+    put_u16(&mut result, 0);
+    segments.iter().for_each(|(start, _, _)| put_u16(&mut result, *start));
+    segments.iter().for_each(|(start, _, glyph)| put_u16(&mut result, glyph.wrapping_sub(*start)));
+    segments.iter().for_each(|_| put_u16(&mut result, 0));
+    Ok(result)
+}
+
+fn cmap_format12(groups: &[(u32, u32, u16)]) -> Vec<u8> {
+    let mut result = Vec::new();
+    put_u16(&mut result, 12);
+    put_u16(&mut result, 0);
+    put_u32(&mut result, 16 + 12 * groups.len() as u32);
+    put_u32(&mut result, 0);
+    put_u32(&mut result, groups.len() as u32);
+    for &(start, end, glyph) in groups {
+        put_u32(&mut result, start);
+        put_u32(&mut result, end);
+        put_u32(&mut result, glyph as u32);
+    }
+    result
+}
+
+fn name(family: &str) -> Vec<u8> {
+    let postscript: String = family
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .take(54)
+        .collect();
+    let records = [
+        (1, family.to_string()),
+        (2, String::from("Regular")),
+        (3, format!("{postscript}-Regular;vglyph")),
+        (4, format!("{family} Regular")),
+        (5, String::from("Version 1.0")),
+        (6, format!("{postscript}-Regular")),
+    ];
+
+    let mut result = Vec::new();
+    let mut strings = Vec::new();
+    put_u16(&mut result, 0);
+    put_u16(&mut result, records.len() as u16);
+    put_u16(&mut result, 6 + 12 * records.len() as u16);
+    for (id, value) in records {
+        let encoded: Vec<u8> = value.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        // windows, unicode bmp, english
+        put_u16(&mut result, 3);
+        put_u16(&mut result, 1);
+        put_u16(&mut result, 0x0409);
+        put_u16(&mut result, id);
+        put_u16(&mut result, encoded.len() as u16);
+        put_u16(&mut result, strings.len() as u16);
+        strings.extend(encoded);
+    }
+    result.extend(strings);
+    result
+}
+
+fn post(scale: Scale, options: &FontOptions) -> Result<Vec<u8>, FontError> {
+    let mut result = Vec::new();
+    // version 3, without glyph names
+    put_u32(&mut result, 0x0003_0000);
+    put_u32(&mut result, 0);
+    put_i16(&mut result, scale.units(-0.1).unwrap_or_default());
+    put_i16(&mut result, scale.metric(options.pen.width, "stroke width")?);
+    result.extend([0; 20]);
+    Ok(result)
+}
+
+fn assemble(tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let (search_range, entry_selector) = search_parameters(count);
+    let mut result = Vec::new();
+    put_u32(&mut result, 0x0001_0000);
+    put_u16(&mut result, count);
+    put_u16(&mut result, search_range * 16);
+    put_u16(&mut result, entry_selector);
+    put_u16(&mut result, count * 16 - search_range * 16);
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = 0;
+    for (tag, data) in &tables {
+        if tag == b"head" {
+            head_offset = offset;
+        }
+        result.extend(tag);
+        put_u32(&mut result, checksum(data));
+        put_u32(&mut result, offset as u32);
+        put_u32(&mut result, data.len() as u32);
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in &tables {
+        result.extend(data);
+        result.resize(result.len().next_multiple_of(4), 0);
+    }
+
+    let adjustment = 0xB1B0_AFBA_u32.wrapping_sub(checksum(&result));
+    result[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    result
+}
+
+/// Largest power of two not above `count`, and its logarithm.
+fn search_parameters(count: u16) -> (u16, u16) {
+    let entry_selector = 15 - count.max(1).leading_zeros() as u16;
+    (1 << entry_selector, entry_selector)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0, u32::wrapping_add)
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend(value.to_be_bytes());
+}
+
+fn put_i16(buffer: &mut Vec<u8>, value: i16) {
+    buffer.extend(value.to_be_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend(value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::compiler::Segment;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_be_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn i16_at(data: &[u8], offset: usize) -> i16 {
+        i16::from_be_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// The tables of a font by tag, checking the directory and every checksum.
+    fn tables(font: &[u8]) -> HashMap<[u8; 4], &[u8]> {
+        assert_eq!(u32_at(font, 0), 0x0001_0000);
+        let count = u16_at(font, 4) as usize;
+        let (search_range, entry_selector) = search_parameters(count as u16);
+        assert_eq!(u16_at(font, 6), search_range * 16);
+        assert_eq!(u16_at(font, 8), entry_selector);
+        assert_eq!(u16_at(font, 10) as usize, count * 16 - search_range as usize * 16);
+
+        let mut result = HashMap::new();
+        let mut previous_tag = [0; 4];
+        for idx in 0..count {
+            let record = 12 + 16 * idx;
+            let tag: [u8; 4] = font[record..record + 4].try_into().unwrap();
+            let (offset, length) = (u32_at(font, record + 8) as usize, u32_at(font, record + 12) as usize);
+            assert!(tag > previous_tag, "tables must be sorted by tag");
+            assert_eq!(offset % 4, 0);
+            let mut data = font[offset..offset + length].to_vec();
+            if &tag == b"head" {
+                // the adjustment is left out of the table's own checksum
+                data[8..12].fill(0);
+            }
+            assert_eq!(u32_at(font, record + 4), checksum(&data), "checksum of {}", String::from_utf8_lossy(&tag));
+            result.insert(tag, &font[offset..offset + length]);
+            previous_tag = tag;
+        }
+        result
+    }
+
+    fn line(points: &[(f32, f32)]) -> Vec<Path> {
+        vec![Path { segments: points.windows(2).map(|pair| Segment::Line(pair[0], pair[1])).collect() }]
+    }
+
+    fn font_of(paths: &[Vec<Path>], codepoints: &[u32], options: &FontOptions) -> Result<Vec<u8>, FontError> {
+        let glyphs: Vec<_> = paths
+            .iter()
+            .zip(codepoints)
+            .map(|(paths, codepoint)| FontGlyph { codepoint: *codepoint, paths })
+            .collect();
+        font(&glyphs, options)
+    }
+
+
+    #[test]
+    fn directory_and_checksums() {
+        let paths = [line(&[(0.2, 0.2), (0.8, 0.8)]), line(&[(0.5, 0.1), (0.5, 0.9)])];
+        let font = font_of(&paths, &[0xE000, 0xE001], &FontOptions::default()).unwrap();
+        let tables = tables(&font);
+        let tags: Vec<_> = tables.keys().copied().collect();
+        assert_eq!(tags.len(), 10);
+
+        let head = tables[b"head"];
+        assert_eq!(u16_at(head, 18), 1000);
+        assert_eq!(u32_at(head, 12), 0x5F0F_3CF5);
+        // with the adjustment in place, the whole file sums up to the magic number
+        assert_eq!(checksum(&font), 0xB1B0_AFBA);
+        assert_eq!(u16_at(tables[b"maxp"], 4), 4);
+        assert_eq!(u16_at(tables[b"hhea"], 34), 4);
+    }
+
+    #[test]
+    fn glyf_and_loca_offsets() {
+        let paths = [line(&[(0.2, 0.2), (0.8, 0.8)]), vec![], line(&[(0.5, 0.1), (0.5, 0.9)])];
+        let font = font_of(&paths, &[0xE000, 0xE001, 0xE002], &FontOptions::default()).unwrap();
+        let tables = tables(&font);
+        let (glyf, loca) = (tables[b"glyf"], tables[b"loca"]);
+        assert_eq!(i16_at(tables[b"head"], 50), 1, "long offsets");
+
+        let offsets: Vec<usize> = (0..6).map(|idx| u32_at(loca, 4 * idx) as usize).collect();
+        assert_eq!(loca.len(), 4 * 6);
+        assert_eq!(offsets[0], 0);
+        assert_eq!(*offsets.last().unwrap(), glyf.len());
+        // the space and the empty glyph have no outline
+        assert_eq!(offsets[1], offsets[2]);
+        assert_eq!(offsets[3], offsets[4]);
+        for window in offsets.windows(2) {
+            assert!(window[0] <= window[1] && window[0] % 4 == 0);
+        }
+
+        // every outline ends at the next offset, with its deltas leading back to the bounds
+        for (start, end) in [(offsets[0], offsets[1]), (offsets[2], offsets[3]), (offsets[4], offsets[5])] {
+            let data = &glyf[start..end];
+            let contours = i16_at(data, 0) as usize;
+            assert!(contours > 0);
+            let points = u16_at(data, 10 + 2 * (contours - 1)) as usize + 1;
+            let flags = 10 + 2 * contours + 2;
+            assert!(data[flags..flags + points].iter().all(|flag| *flag == 0x01));
+            let xs = flags + points;
+            let ys = xs + 2 * points;
+            assert!(end - start - (ys + 2 * points) < 4);
+            let (mut x, mut y) = (0, 0);
+            let (mut x_min, mut y_min, mut x_max, mut y_max) = (i16::MAX, i16::MAX, i16::MIN, i16::MIN);
+            for idx in 0..points {
+                x += i16_at(data, xs + 2 * idx);
+                y += i16_at(data, ys + 2 * idx);
+                (x_min, y_min, x_max, y_max) = (x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y));
+            }
+            assert_eq!([i16_at(data, 2), i16_at(data, 4), i16_at(data, 6), i16_at(data, 8)], [x_min, y_min, x_max, y_max]);
+        }
+
+        // the diagonal reaches from 0.2 to 0.8, widened by half the pen on both sides
+        let data = &glyf[offsets[2]..];
+        assert_eq!([i16_at(data, 2), i16_at(data, 8)], [175, 625]);
+    }
+
+    #[test]
+    fn cmap_format4_segments() {
+        let paths = [line(&[(0.2, 0.2), (0.8, 0.8)]), line(&[(0.2, 0.8), (0.8, 0.2)]), line(&[(0.5, 0.1), (0.5, 0.9)]), line(&[(0.1, 0.5), (0.9, 0.5)])];
+        let font = font_of(&paths, &[0x41, 0x42, 0xE000, 0x1F600], &FontOptions::default()).unwrap();
+        let cmap = tables(&font)[b"cmap"];
+        assert_eq!(u16_at(cmap, 2), 2);
+        let format4 = u32_at(cmap, 8) as usize;
+        let subtable = &cmap[format4..];
+        assert_eq!(u16_at(subtable, 0), 4);
+        let segments = u16_at(subtable, 6) as usize / 2;
+        assert_eq!(u16_at(subtable, 2) as usize, 16 + 8 * segments);
+
+        let read = |array: usize, idx: usize| u16_at(subtable, 14 + array * (2 * segments) + 2 * array.min(1) + 2 * idx);
+        let parsed: Vec<(u16, u16, u16)> = (0..segments).map(|idx| (read(1, idx), read(0, idx), read(2, idx))).collect();
+        // space, the run A-B, the private use glyph and the final segment, without U+1F600
+        assert_eq!(parsed, [
+            (0x20, 0x20, 1u16.wrapping_sub(0x20)),
+            (0x41, 0x42, 2u16.wrapping_sub(0x41)),
+            (0xE000, 0xE000, 4u16.wrapping_sub(0xE000)),
+            (0xFFFF, 0xFFFF, 1),
+        ]);
+        assert!((0..segments).all(|idx| read(3, idx) == 0));
+
+        // format 12 has everything
+        let format12 = &cmap[u32_at(cmap, 16) as usize..];
+        assert_eq!(u16_at(format12, 0), 12);
+        let groups: Vec<_> = (0..u32_at(format12, 12) as usize)
+            .map(|idx| (u32_at(format12, 16 + 12 * idx), u32_at(format12, 20 + 12 * idx), u32_at(format12, 24 + 12 * idx)))
+            .collect();
+        assert_eq!(groups, [(0x20, 0x20, 1), (0x41, 0x42, 2), (0xE000, 0xE000, 4), (0x1F600, 0x1F600, 5)]);
+    }
+
+    #[test]
+    fn glyphs_can_take_the_space() {
+        let paths = [line(&[(0.2, 0.2), (0.8, 0.8)])];
+        let font = font_of(&paths, &[0x20], &FontOptions::default()).unwrap();
+        let cmap = tables(&font)[b"cmap"];
+        let format12 = &cmap[u32_at(cmap, 16) as usize..];
+        assert_eq!(u32_at(format12, 12), 1);
+        assert_eq!(u32_at(format12, 24), 2);
+    }
+
+    #[test]
+    fn out_of_range_fonts_are_rejected() {
+        let paths = [line(&[(0.2, 0.2), (0.8, 0.8)]), line(&[(-1.5, 0.5), (1.5, 0.5)])];
+        let options = FontOptions::default();
+        assert_eq!(font_of(&paths, &[0xE000, 0xE000], &options), Err(FontError::DuplicateCodepoint(0xE000)));
+        assert_eq!(font_of(&[], &[], &FontOptions { units_per_em: 0, ..options.clone() }), Err(FontError::UnitsPerEm(0)));
+        assert_eq!(font_of(&[], &[], &FontOptions { units_per_em: 16385, ..options.clone() }), Err(FontError::UnitsPerEm(16385)));
+
+        let large = FontOptions { units_per_em: 16384, ..options.clone() };
+        assert!(font_of(&paths[..1], &[0xE000], &large).is_ok());
+        // every point fits, but not the step from one end of the line to the other
+        assert_eq!(font_of(&paths, &[0xE000, 0xE001], &large), Err(FontError::CoordinateOutOfRange(Some(0xE001))));
+        let far = [line(&[(2.5, 0.5), (2.6, 0.5)])];
+        assert_eq!(font_of(&far, &[0xE000], &large), Err(FontError::CoordinateOutOfRange(Some(0xE000))));
+        assert_eq!(font_of(&[], &[], &FontOptions { advance: 2.5, ..large.clone() }), Err(FontError::MetricOutOfRange("advance")));
+        assert_eq!(font_of(&[], &[], &FontOptions { advance: -0.5, ..large }), Err(FontError::MetricOutOfRange("advance")));
+    }
+
+    #[test]
+    fn glyphs_with_too_many_points_are_rejected() {
+        let mut points: Vec<(f32, f32)> = (0..70_000).map(|idx| (idx as f32 * 0.01 % 1.0, (idx / 100) as f32 * 0.001)).collect();
+        points.dedup();
+        let outline = outline(&[points], |(x, y)| Some(((x * 1000.0) as i16, (y * 1000.0) as i16)), Some(0xE000));
+        assert!(matches!(outline, Err(FontError::TooManyPoints(Some(0xE000)))));
+    }
+}
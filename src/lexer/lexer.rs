@@ -1,111 +1,371 @@
-use std::iter::Peekable;
-use std::str::Chars;
-use crate::lexer::Token;
+use crate::lexer::{LexError, Span, SpannedToken, Token};
 
+/// Splits a `.glyph` source into tokens, skipping whitespace and comments. Malformed input
+/// becomes [`Token::Error`] and lexing continues after it.
 pub struct Lexer<'a> {
-    chars: Peekable<Chars<'a>>,
+    src: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+    trivia: bool,
 }
 
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Lexer { src, offset: 0, line: 1, column: 1, trivia: false }
+    }
+
+    /// Lossless lexer that also yields whitespace and `#` comments, so the spans cover the whole source.
+    pub fn with_trivia(src: &'a str) -> Self {
+        Lexer { trivia: true, ..Lexer::new(src) }
+    }
 
+    /// Empty span at the current position, e.g. to point at the end of input.
+    pub fn current_span(&self) -> Span {
+        Span { start: self.offset, end: self.offset, line: self.line, column: self.column }
+    }
 
-impl<'a> Lexer<'a> {
-    pub fn new(chars: &'a str) -> Self {
-        Lexer { chars: chars.chars().peekable() }
+    fn peek(&self) -> Option<char> {
+        self.src[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn bump_if(&mut self, predicate: impl FnOnce(&char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if predicate(&c) => self.bump(),
+            _ => None,
+        }
     }
 
     fn skip_whitespace(&mut self) {
-        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
-    }
-
-    fn parse_nontrivial_operator(&mut self) -> Option<Token> {
-        match self.chars.next()? {
-            '-' => self.chars.next()?.eq(&'>').then_some(Token::Arrow),
-            '~' => self.chars.next()?.eq(&'>').then_some(Token::TildeArrow),
-            '=' => self.chars.next()?.eq(&'>').then_some(Token::WideArrow),
-            ':' => self.chars.next()?.eq(&'=').then_some(Token::Assign),
-            '[' => match self.chars.next_if(|c| *c == '[') {
-                Some(_) => Some(Token::LWideBracket),
-                None => Some(Token::LBracket),
+        while self.bump_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn parse_nontrivial_operator(&mut self) -> Token {
+        let first = self.bump().unwrap();
+        let (second, token) = match first {
+            '-' => ('>', Token::Arrow),
+            '~' => ('>', Token::TildeArrow),
+            '=' => ('>', Token::WideArrow),
+            ':' => ('=', Token::Assign),
+            '[' => return match self.bump_if(|c| *c == '[') {
+                Some(_) => Token::LWideBracket,
+                None => Token::LBracket,
             },
-            ']' => match self.chars.next_if(|c| *c == ']') {
-                Some(_) => Some(Token::RWideBracket),
-                None => Some(Token::RBracket),
+            ']' => return match self.bump_if(|c| *c == ']') {
+                Some(_) => Token::RWideBracket,
+                None => Token::RBracket,
             },
-            _ => None,
+            _ => unreachable!(),
+        };
+        match self.bump_if(|c| *c == second) {
+            Some(_) => token,
+            None => Token::Error(LexError::UnexpectedCharacter(first)),
+        }
+    }
+
+    /// Whether a number starts here. A `-` directly followed by a digit or `.` is a sign rather than
+    /// the start of `->`; a leading `+` is only a sign for the second coordinate of a pair, since
+    /// it would otherwise be ambiguous with `Plus`.
+    fn starts_number(&self, plus_is_sign: bool) -> bool {
+        let mut chars = self.src[self.offset..].chars();
+        match chars.next() {
+            Some('0' ..= '9' | '.') => true,
+            Some('-') => matches!(chars.next(), Some('0' ..= '9' | '.')),
+            Some('+') if plus_is_sign => matches!(chars.next(), Some('0' ..= '9' | '.')),
+            _ => false,
+        }
+    }
+
+    fn starts_exponent(&self) -> bool {
+        let mut chars = self.src[self.offset..].chars();
+        matches!(chars.next(), Some('e' | 'E')) && match chars.next() {
+            Some('+' | '-') => matches!(chars.next(), Some('0' ..= '9')),
+            next => matches!(next, Some('0' ..= '9')),
         }
     }
 
-    fn parse_number(&mut self) -> Option<f32> {
+    fn parse_number(&mut self) -> Result<f32, LexError> {
         let mut number = String::new();
 
-        while let Some(c) = self.chars.next_if(|c| c.is_digit(10) || *c == '.') {
+        if let Some(sign) = self.bump_if(|c| *c == '-' || *c == '+') {
+            number.push(sign);
+        }
+        while let Some(c) = self.bump_if(|c| c.is_ascii_digit() || *c == '.') {
             number.push(c);
         }
-
-        match number.parse::<f32>() {
-            Ok(n) => Some(n),
-            Err(_) => None,
+        if self.starts_exponent() {
+            number.extend(self.bump());
+            number.extend(self.bump_if(|c| *c == '-' || *c == '+'));
+            while let Some(c) = self.bump_if(|c| c.is_ascii_digit()) {
+                number.push(c);
+            }
+        }
+        // swallow whatever else is glued to the number, so `0.1.2` or `1x` is reported as a whole
+        while let Some(c) = self.bump_if(|c| c.is_alphanumeric() || *c == '.' || *c == '_') {
+            number.push(c);
         }
+
+        number.parse::<f32>().map_err(|_| LexError::MalformedNumber(number))
     }
 
-    fn parse_value_pair(&mut self) -> Option<Token> {
-        let first = self.parse_number()?;
+    fn parse_value_pair(&mut self) -> Token {
+        let first = match self.parse_number() {
+            Ok(first) => first,
+            Err(error) => return Token::Error(error),
+        };
         self.skip_whitespace();
-        let second = self.parse_number()?;
-        Some(Token::ValuePair(first, second))
+        if !self.starts_number(true) {
+            return Token::Error(LexError::MissingCoordinate);
+        }
+        match self.parse_number() {
+            Ok(second) => Token::ValuePair(first, second),
+            Err(error) => Token::Error(error),
+        }
+    }
+
+    /// Reads the escape after a `\` in a quoted identifier: `\"`, `\\`, `\n`, `\t` or `\u{XXXX}`.
+    fn parse_escape(&mut self) -> Result<char, LexError> {
+        match self.bump() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let mut escape = String::from("u");
+                if self.bump_if(|c| *c == '{').is_none() {
+                    return Err(LexError::InvalidEscape(escape));
+                }
+                escape.push('{');
+                while let Some(c) = self.bump_if(|c| c.is_ascii_hexdigit()) {
+                    escape.push(c);
+                }
+                if self.bump_if(|c| *c == '}').is_none() {
+                    return Err(LexError::InvalidEscape(escape));
+                }
+                escape.push('}');
+                u32::from_str_radix(&escape[2..escape.len() - 1], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(LexError::InvalidEscape(escape))
+            },
+            Some('\n') | None => Err(LexError::UnterminatedIdent),
+            Some(c) => Err(LexError::InvalidEscape(c.to_string())),
+        }
+    }
+
+    fn parse_quoted_ident(&mut self) -> Token {
+        let mut result = String::new();
+        let mut error = None;
+        loop {
+            match self.bump_if(|c| *c != '\n') {
+                Some('"') => break,
+                Some('\\') => match self.parse_escape() {
+                    Ok(c) => result.push(c),
+                    Err(LexError::UnterminatedIdent) => return Token::Error(LexError::UnterminatedIdent),
+                    Err(e) => error = error.or(Some(e)),
+                },
+                Some(c) => result.push(c),
+                None => return Token::Error(LexError::UnterminatedIdent),
+            }
+        }
+        match error {
+            Some(error) => Token::Error(error),
+            None => Token::Ident(result),
+        }
     }
 
-    fn parse_ident(&mut self) -> Option<Token> {
-        match self.chars.next_if(|c| *c == '"') {
-            Some(_) => todo!(),
+    fn parse_ident(&mut self) -> Token {
+        match self.bump_if(|c| *c == '"') {
+            Some(_) => self.parse_quoted_ident(),
             None => {
                 let mut result = String::new();
-                while let Some(c) = self.chars.next_if(|c| c.is_alphabetic() || *c == '_' || *c == '-') {
+                while let Some(c) = self.bump_if(|c| c.is_alphabetic() || *c == '_' || *c == '-') {
                     result.push(c);
                 }
-                Some(Token::Ident(result))
+                Token::Ident(result)
             },
         }
     }
 
     fn skip_comment(&mut self) {
-        while let Some('#') = self.chars.peek() {
-            while !matches!(self.chars.peek(), Some('\n')) {
-                self.chars.next();
-            }
+        while let Some('#') = self.peek() {
+            self.parse_comment();
             self.skip_whitespace();
         }
     }
+
+    fn parse_comment(&mut self) -> Token {
+        let _ = self.bump_if(|c| *c == '#');
+        let mut result = String::new();
+        while let Some(c) = self.bump_if(|c| *c != '\n') {
+            result.push(c);
+        }
+        Token::Comment(result)
+    }
+
+    fn parse_trivia(&mut self) -> Option<Token> {
+        match self.peek()? {
+            '#' => Some(self.parse_comment()),
+            c if c.is_whitespace() => {
+                self.skip_whitespace();
+                Some(Token::Whitespace)
+            },
+            _ => None,
+        }
+    }
+
+    fn parse_token(&mut self) -> Option<Token> {
+        match self.peek()? {
+            _ if self.starts_number(false) => return Some(self.parse_value_pair()),
+            '~' | '-' | '=' | ':' | '[' | ']' => return Some(self.parse_nontrivial_operator()),
+            c if c.is_alphabetic() || c == '"' || c == '_' => return Some(self.parse_ident()),
+            _ => {},
+        }
+
+        Some(match self.bump()? {
+            ',' => Token::Comma,
+            '+' => Token::Plus,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LCurly,
+            '}' => Token::RCurly,
+            '|' => Token::Pipe,
+            '/' => Token::Slash,
+            '?' => Token::QuestionMark,
+            ';' => Token::Semicolon,
+            '§' => Token::Section,
+            '&' => Token::Ampersand,
+            c => Token::Error(LexError::UnexpectedCharacter(c)),
+        })
+    }
 }
 
 impl Iterator for Lexer<'_> {
-    type Item = Token;
+    type Item = SpannedToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
-        self.skip_comment();
-
-        match self.chars.peek()? {
-            '~' | '-' | '=' | ':' | '[' | ']' => return self.parse_nontrivial_operator(),
-            '.' | '0' ..= '9' => return self.parse_value_pair(),
-            c if c.is_alphabetic() || *c == '"' || *c == '_' => return self.parse_ident(),
-            _ => {},
+        if !self.trivia {
+            self.skip_whitespace();
+            self.skip_comment();
         }
 
-        match self.chars.next()? {
-            ',' => Some(Token::Comma),
-            '+' => Some(Token::Plus),
-            '(' => Some(Token::LParen),
-            ')' => Some(Token::RParen),
-            '{' => Some(Token::LCurly),
-            '}' => Some(Token::RCurly),
-            '|' => Some(Token::Pipe),
-            '/' => Some(Token::Slash),
-            '?' => Some(Token::QuestionMark),
-            ';' => Some(Token::Semicolon),
-            '§' => Some(Token::Section),
-            '&' => Some(Token::Ampersand),
-            _ => None,
+        let start = self.current_span();
+        let token = match self.trivia.then(|| self.parse_trivia()).flatten() {
+            Some(trivia) => trivia,
+            None => self.parse_token()?,
+        };
+        Some(SpannedToken { token, span: start.to(self.current_span()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(src: &str) -> Vec<Token> {
+        Lexer::new(src).map(|spanned| spanned.token).collect()
+    }
+
+    #[test]
+    fn skips_whitespace_and_comments() {
+        let expected = [
+            Token::Ident(String::from("a")),
+            Token::Assign,
+            Token::ValuePair(0.0, 0.0),
+            Token::Arrow,
+            Token::ValuePair(1.0, 1.0),
+            Token::Semicolon,
+        ];
+        assert_eq!(tokens("# first\na := 0 0 -> 1 1; # last"), expected);
+    }
+
+    #[test]
+    fn trivia_covers_the_whole_source() {
+        let src = "# radical\n§ a, b := (0 0 -> 1 1) | a;  # trailing\n\n";
+        let spanned: Vec<SpannedToken> = Lexer::with_trivia(src).collect();
+        let mut end = 0;
+        for token in &spanned {
+            assert_eq!(token.span.start, end);
+            end = token.span.end;
         }
+        assert_eq!(end, src.len());
+        let text: String = spanned.iter().map(|token| token.span.text(src)).collect();
+        assert_eq!(text, src);
+
+        assert_eq!(spanned[0].token, Token::Comment(String::from(" radical")));
+        assert_eq!(spanned[1].token, Token::Whitespace);
+        let trivia: Vec<_> = spanned.iter().filter(|token| token.token.is_trivia()).collect();
+        assert_eq!(spanned.len() - trivia.len(), tokens(src).len());
+    }
+
+    #[test]
+    fn spans_count_lines_and_characters() {
+        let src = "§ a :=\n  0 0 -> 1 1;";
+        let spanned: Vec<SpannedToken> = Lexer::new(src).collect();
+        assert_eq!(spanned[1].span, Span { start: 3, end: 4, line: 1, column: 3 });
+        assert_eq!(spanned[3].span, Span { start: 10, end: 13, line: 2, column: 3 });
+        assert_eq!(spanned[3].span.text(src), "0 0");
+        assert_eq!(Lexer::new(src).last().unwrap().span.column, 13);
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        assert_eq!(tokens(r#""two words""#), [Token::Ident(String::from("two words"))]);
+        assert_eq!(tokens(r#""say \"hi\"\\""#), [Token::Ident(String::from("say \"hi\"\\"))]);
+        assert_eq!(tokens(r#""\n\t\u{263A}""#), [Token::Ident(String::from("\n\t\u{263A}"))]);
+        assert_eq!(tokens(r#""" a"#), [Token::Ident(String::new()), Token::Ident(String::from("a"))]);
+    }
+
+    #[test]
+    fn malformed_quoted_identifiers() {
+        assert_eq!(tokens(r#""a\qb" c"#), [Token::Error(LexError::InvalidEscape(String::from("q"))), Token::Ident(String::from("c"))]);
+        assert_eq!(tokens(r#""\u{D800}""#), [Token::Error(LexError::InvalidEscape(String::from("u{D800}")))]);
+        assert_eq!(tokens(r#""\u263A""#), [Token::Error(LexError::InvalidEscape(String::from("u")))]);
+        assert_eq!(tokens("\"open\na;"), [
+            Token::Error(LexError::UnterminatedIdent),
+            Token::Ident(String::from("a")),
+            Token::Semicolon,
+        ]);
+        assert_eq!(tokens(r#""open\"#), [Token::Error(LexError::UnterminatedIdent)]);
+    }
+
+    #[test]
+    fn signed_and_scientific_numbers() {
+        assert_eq!(tokens("-0.5 +1"), [Token::ValuePair(-0.5, 1.0)]);
+        assert_eq!(tokens(".5 1e-1"), [Token::ValuePair(0.5, 0.1)]);
+        assert_eq!(tokens("2.5E+1 -3e2"), [Token::ValuePair(25.0, -300.0)]);
+        // `e` without digits belongs to the next identifier
+        assert_eq!(tokens("1 2 e"), [Token::ValuePair(1.0, 2.0), Token::Ident(String::from("e"))]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn signs_next_to_operators() {
+        assert_eq!(tokens("0 0->-1 -1"), [Token::ValuePair(0.0, 0.0), Token::Arrow, Token::ValuePair(-1.0, -1.0)]);
+        // a leading `+` is the operator, only the second coordinate takes it as a sign
+        assert_eq!(tokens("a +1 1"), [Token::Ident(String::from("a")), Token::Plus, Token::ValuePair(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        assert_eq!(tokens("0.1.2 3 4"), [Token::Error(LexError::MalformedNumber(String::from("0.1.2"))), Token::ValuePair(3.0, 4.0)]);
+        assert_eq!(tokens("1 2x"), [Token::Error(LexError::MalformedNumber(String::from("2x")))]);
+        assert_eq!(tokens("1;"), [Token::Error(LexError::MissingCoordinate), Token::Semicolon]);
+        assert_eq!(tokens("1 -> 2"), [
+            Token::Error(LexError::MissingCoordinate),
+            Token::Arrow,
+            Token::Error(LexError::MissingCoordinate),
+        ]);
+    }
+}
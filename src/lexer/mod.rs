@@ -1,5 +1,7 @@
+//! Tokens of `.glyph` sources, each with the [`Span`] it was read from.
+
 mod lexer;
 mod token;
 
 pub use lexer::Lexer;
-pub use token::Token;
\ No newline at end of file
+pub use token::{LexError, Span, SpannedToken, Token};
@@ -1,6 +1,11 @@
-#[derive(Debug, PartialEq)]
+use std::fmt;
+
+/// A token, displayed as it is spelled in the source.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
+    /// A name, either bare or `"quoted"` with its escapes resolved.
     Ident(String),
+    /// Coordinates `x y`.
     ValuePair(f32, f32),
     Arrow,
     TildeArrow,
@@ -22,4 +27,97 @@ pub enum Token {
     Semicolon,
     Section,
     Ampersand,
-}
\ No newline at end of file
+    /// Only produced by [`Lexer::with_trivia`](crate::lexer::Lexer::with_trivia).
+    Whitespace,
+    /// Only produced by [`Lexer::with_trivia`](crate::lexer::Lexer::with_trivia), without the leading `#`.
+    Comment(String),
+    /// Malformed input, reported in place of a token.
+    Error(LexError),
+}
+
+impl Token {
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace | Token::Comment(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnexpectedCharacter(char),
+    MissingCoordinate,
+    MalformedNumber(String),
+    UnterminatedIdent,
+    InvalidEscape(String),
+}
+
+/// Byte range of a token in the source, plus the 1-based line and column it starts at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end, ..self }
+    }
+
+    /// The source text covered by the span, `src` being what it was lexed from.
+    pub fn text(self, src: &str) -> &str {
+        &src[self.start..self.end]
+    }
+}
+
+/// A token and where it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "identifier `{ident}`"),
+            Token::ValuePair(x, y) => write!(f, "coordinates `{x} {y}`"),
+            Token::Arrow => write!(f, "`->`"),
+            Token::TildeArrow => write!(f, "`~>`"),
+            Token::WideArrow => write!(f, "`=>`"),
+            Token::Assign => write!(f, "`:=`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::LCurly => write!(f, "`{{`"),
+            Token::RCurly => write!(f, "`}}`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Slash => write!(f, "`/`"),
+            Token::LBracket => write!(f, "`[`"),
+            Token::RBracket => write!(f, "`]`"),
+            Token::LWideBracket => write!(f, "`[[`"),
+            Token::RWideBracket => write!(f, "`]]`"),
+            Token::QuestionMark => write!(f, "`?`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Section => write!(f, "`§`"),
+            Token::Ampersand => write!(f, "`&`"),
+            Token::Whitespace => write!(f, "whitespace"),
+            Token::Comment(_) => write!(f, "comment"),
+            Token::Error(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::UnexpectedCharacter(c) => write!(f, "unexpected character `{c}`"),
+            LexError::MissingCoordinate => write!(f, "expected a second coordinate"),
+            LexError::MalformedNumber(number) => write!(f, "malformed number `{number}`"),
+            LexError::UnterminatedIdent => write!(f, "unterminated quoted identifier"),
+            LexError::InvalidEscape(escape) => write!(f, "invalid escape sequence `\\{escape}`"),
+        }
+    }
+}
@@ -1,10 +1,14 @@
-#![feature(box_patterns)]
+#![allow(clippy::module_inception)]
 
 mod compiler;
-pub(crate) mod lexer;
-pub(crate) mod parser;
-
-pub use compiler::compile;
-
-
-
+mod diagnostic;
+pub mod export;
+pub mod outline;
+pub mod raster;
+pub mod lexer;
+pub mod parser;
+
+pub use compiler::{compile, compile_glyphs, compile_partial, flatten, Glyph, GlyphSet, Line, Path, Segment};
+pub use diagnostic::{Diagnostic, ErrorKind};
+pub use lexer::{LexError, Lexer, Span, SpannedToken, Token};
+pub use parser::{parse, Annotations, Character, Parser, SyntaxTree};
@@ -1,12 +1,10 @@
-use vglyph::compile;
+mod cli;
 
-// TODO: run cli from here
+use std::env;
+use std::process::ExitCode;
 
-fn main() {
-    let input = include_str!("../res/test_inner.glyph");
-    let map = compile(input);
-
-    dbg!(&map);
+fn main() -> ExitCode {
+    cli::run(env::args().skip(1))
 }
 
 
@@ -0,0 +1,40 @@
+mod pen;
+mod union;
+
+use crate::compiler::Path;
+
+pub use pen::{LineCap, LineJoin, Pen};
+
+/// Closed polygon, the last point connects back to the first. Filled regions have a positive
+/// signed area in glyph coordinates (y pointing down), i.e. they run clockwise on screen, holes
+/// run the other way.
+pub type Contour = Vec<(f32, f32)>;
+
+/// Expands the centre lines of a character into the closed outline of the area `pen` covers
+/// when drawing them. Curves are flattened to within `tolerance` first. Overlapping strokes are
+/// merged, so the result can be filled with either the nonzero or the even-odd rule.
+pub fn stroke(paths: &[Path], pen: &Pen, tolerance: f32) -> Vec<Contour> {
+    let pieces: Vec<Contour> = paths
+        .iter()
+        .flat_map(|path| pen.pieces(&path.flatten(tolerance)))
+        .collect();
+    union::union(&pieces)
+}
+
+/// Reverses `contour` if needed, so it fills its inside.
+fn oriented(mut contour: Contour) -> Contour {
+    if signed_area(&contour) < 0.0 {
+        contour.reverse();
+    }
+    contour
+}
+
+pub fn signed_area(contour: &[(f32, f32)]) -> f32 {
+    let mut result = 0.0;
+    for idx in 0..contour.len() {
+        let (x0, y0) = contour[idx];
+        let (x1, y1) = contour[(idx + 1) % contour.len()];
+        result += x0 * y1 - x1 * y0;
+    }
+    result / 2.0
+}
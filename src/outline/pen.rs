@@ -0,0 +1,255 @@
+use std::f32::consts::PI;
+use std::str::FromStr;
+use crate::outline::{oriented, Contour};
+
+/// Largest angle covered by a single edge of a round cap or join.
+const ARC_STEP: f32 = PI / 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pen {
+    /// Stroke width relative to the glyph square.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// Miter joins longer than `miter_limit * width` fall back to bevel joins, as in SVG.
+    pub miter_limit: f32,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Pen {
+            width: 0.05,
+            cap: LineCap::Round,
+            join: LineJoin::Round,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl Pen {
+    /// Covers the stroke of `points` with overlapping convex pieces: one quad per segment, plus
+    /// the joins between segments and the caps at the ends. A closed polyline repeats its first
+    /// point at the end and gets a join there instead of caps. A single point is a dot drawn by
+    /// the caps, like a zero length path in SVG, no points at all draw nothing.
+    pub(super) fn pieces(&self, points: &[(f32, f32)]) -> Vec<Contour> {
+        let half = self.width / 2.0;
+        let mut points = points.to_vec();
+        points.dedup();
+        let mut result = Vec::new();
+
+        if points.is_empty() {
+            return result;
+        }
+        if points.len() == 1 {
+            let p = points[0];
+            match self.cap {
+                LineCap::Butt => {},
+                LineCap::Round => result.push(arc(p, (half, 0.0), 2.0 * PI, false)),
+                LineCap::Square => result.push(vec![
+                    (p.0 - half, p.1 - half),
+                    (p.0 + half, p.1 - half),
+                    (p.0 + half, p.1 + half),
+                    (p.0 - half, p.1 + half),
+                ]),
+            }
+            return result;
+        }
+
+        let directions: Vec<_> = points.windows(2).map(|pair| direction(pair[0], pair[1])).collect();
+        for (pair, d) in points.windows(2).zip(&directions) {
+            let (from, to) = (pair[0], pair[1]);
+            let n = normal(*d, half);
+            result.push(oriented(vec![
+                (from.0 + n.0, from.1 + n.1),
+                (to.0 + n.0, to.1 + n.1),
+                (to.0 - n.0, to.1 - n.1),
+                (from.0 - n.0, from.1 - n.1),
+            ]));
+        }
+        for (idx, pair) in directions.windows(2).enumerate() {
+            result.extend(self.join(points[idx + 1], pair[0], pair[1]));
+        }
+
+        let closed = points.len() > 2 && points[0] == points[points.len() - 1];
+        if closed {
+            result.extend(self.join(points[0], directions[directions.len() - 1], directions[0]));
+        } else {
+            let start = directions[0];
+            result.extend(self.cap(points[0], (-start.0, -start.1)));
+            result.extend(self.cap(points[points.len() - 1], directions[directions.len() - 1]));
+        }
+        result
+    }
+
+    /// The piece filling the outer side of the corner at `p`, where direction `d0` turns into `d1`.
+    fn join(&self, p: (f32, f32), d0: (f32, f32), d1: (f32, f32)) -> Option<Contour> {
+        let half = self.width / 2.0;
+        let cross = d0.0 * d1.1 - d0.1 * d1.0;
+        let dot = d0.0 * d1.0 + d0.1 * d1.1;
+        if cross.abs() < 1e-6 && dot > 0.0 {
+            return None;
+        }
+
+        // the outer side lies opposite of the turn, a full reversal goes around the front
+        let side = if cross >= 0.0 { -1.0 } else { 1.0 };
+        let (n0, n1) = (normal(d0, half * side), normal(d1, half * side));
+        let a = (p.0 + n0.0, p.1 + n0.1);
+        let b = (p.0 + n1.0, p.1 + n1.1);
+        let contour = match self.join {
+            LineJoin::Round => {
+                let mut contour = arc(p, n0, cross.atan2(dot), true);
+                contour.push(p);
+                contour
+            },
+            LineJoin::Miter if (2.0 / (1.0 + dot)).sqrt() <= self.miter_limit => {
+                let scale = 1.0 / (1.0 + dot);
+                let miter = (p.0 + (n0.0 + n1.0) * scale, p.1 + (n0.1 + n1.1) * scale);
+                vec![p, a, miter, b]
+            },
+            LineJoin::Miter | LineJoin::Bevel => vec![p, a, b],
+        };
+        Some(oriented(contour))
+    }
+
+    /// The cap at the end point `p` of a stroke leaving in `direction`.
+    fn cap(&self, p: (f32, f32), direction: (f32, f32)) -> Option<Contour> {
+        let half = self.width / 2.0;
+        let n = normal(direction, half);
+        match self.cap {
+            LineCap::Butt => None,
+            LineCap::Round => Some(oriented(arc(p, (-n.0, -n.1), PI, true))),
+            LineCap::Square => {
+                let o = (direction.0 * half, direction.1 * half);
+                Some(oriented(vec![
+                    (p.0 + n.0, p.1 + n.1),
+                    (p.0 + n.0 + o.0, p.1 + n.1 + o.1),
+                    (p.0 - n.0 + o.0, p.1 - n.1 + o.1),
+                    (p.0 - n.0, p.1 - n.1),
+                ]))
+            },
+        }
+    }
+}
+
+fn direction(from: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    (dx / length, dy / length)
+}
+
+/// `direction` turned by a quarter (from x towards y) and scaled to `length`.
+fn normal(direction: (f32, f32), length: f32) -> (f32, f32) {
+    (-direction.1 * length, direction.0 * length)
+}
+
+/// Points on the circle around `center`, starting at `center + start` and turning by `sweep`.
+/// With `include_end` the point at the end of the sweep is part of the result.
+fn arc(center: (f32, f32), start: (f32, f32), sweep: f32, include_end: bool) -> Contour {
+    let steps = (sweep.abs() / ARC_STEP).ceil().max(1.0) as usize;
+    let last = if include_end { steps } else { steps - 1 };
+    (0..=last)
+        .map(|idx| {
+            let (sin, cos) = (sweep * idx as f32 / steps as f32).sin_cos();
+            (center.0 + start.0 * cos - start.1 * sin, center.1 + start.0 * sin + start.1 * cos)
+        })
+        .collect()
+}
+
+impl LineCap {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        }
+    }
+}
+
+impl LineJoin {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        }
+    }
+}
+
+impl FromStr for LineCap {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "butt" => Ok(LineCap::Butt),
+            "round" => Ok(LineCap::Round),
+            "square" => Ok(LineCap::Square),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for LineJoin {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "miter" => Ok(LineJoin::Miter),
+            "round" => Ok(LineJoin::Round),
+            "bevel" => Ok(LineJoin::Bevel),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Path, Segment};
+    use crate::outline::{signed_area, stroke};
+
+    #[test]
+    fn nothing_to_stroke() {
+        let pen = Pen::default();
+        assert!(pen.pieces(&[]).is_empty());
+        assert!(stroke(&[Path { segments: vec![] }], &pen, 0.001).is_empty());
+        assert!(stroke(&[], &pen, 0.001).is_empty());
+    }
+
+    #[test]
+    fn single_points_are_dots_drawn_by_the_caps() {
+        let dot = |cap| Pen { cap, ..Pen::default() }.pieces(&[(0.5, 0.5), (0.5, 0.5)]);
+        assert!(dot(LineCap::Butt).is_empty());
+        assert_eq!(dot(LineCap::Square), [vec![(0.475, 0.475), (0.525, 0.475), (0.525, 0.525), (0.475, 0.525)]]);
+        let round = dot(LineCap::Round);
+        assert_eq!(round.len(), 1);
+        assert!((signed_area(&round[0]) - PI * 0.025 * 0.025).abs() < 1e-4);
+
+        let segment = Path { segments: vec![Segment::Line((0.5, 0.5), (0.5, 0.5))] };
+        assert_eq!(stroke(&[segment], &Pen::default(), 0.001).len(), 1);
+    }
+
+    #[test]
+    fn pieces_fill_their_inside() {
+        for join in [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel] {
+            for cap in [LineCap::Butt, LineCap::Round, LineCap::Square] {
+                let pen = Pen { cap, join, ..Pen::default() };
+                let pieces = pen.pieces(&[(0.1, 0.1), (0.9, 0.1), (0.5, 0.9), (0.1, 0.1)]);
+                assert!(pieces.iter().all(|piece| signed_area(piece) >= 0.0), "{pen:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+use crate::outline::{signed_area, Contour};
+
+/// Computations run in double precision, so that edges meeting at shallow angles still cross
+/// in the right place.
+type Point = (f64, f64);
+type Edge = (Point, Point);
+
+/// Points closer than this are considered the same, it stays above the rounding errors of the
+/// single precision input.
+const EPSILON: f64 = 1e-6;
+
+/// Merges convex, positively oriented, possibly overlapping polygons into the contours of the
+/// area covered by any of them. Outer contours keep the positive orientation, holes come out
+/// negative.
+pub(super) fn union(polygons: &[Contour]) -> Vec<Contour> {
+    // degenerate polygons would claim to cover one side of their edges
+    let polygons: Vec<Vec<Point>> = polygons
+        .iter()
+        .filter(|polygon| signed_area(polygon) > 1e-10)
+        .map(|polygon| polygon.iter().map(|(x, y)| (*x as f64, *y as f64)).collect())
+        .collect();
+    let edges: Vec<Edge> = polygons
+        .iter()
+        .flat_map(|polygon| (0..polygon.len()).map(move |idx| (polygon[idx], polygon[(idx + 1) % polygon.len()])))
+        .filter(|(from, to)| distance(*from, *to) > EPSILON)
+        .collect();
+
+    let mut splits: Vec<Vec<(f64, Point)>> = vec![Vec::new(); edges.len()];
+    for i in 0..edges.len() {
+        for j in i + 1..edges.len() {
+            let (head, tail) = splits.split_at_mut(j);
+            intersect(edges[i], edges[j], &mut head[i], &mut tail[0]);
+        }
+    }
+
+    let mut vertices = Vertices::default();
+    let mut boundary = Vec::new();
+    let mut seen = HashSet::new();
+    for (edge, mut split) in edges.iter().zip(splits) {
+        split.push((0.0, edge.0));
+        split.push((1.0, edge.1));
+        split.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for pair in split.windows(2) {
+            let piece = (pair[0].1, pair[1].1);
+            if distance(piece.0, piece.1) <= EPSILON {
+                continue;
+            }
+            let (mut left, mut right) = (false, false);
+            for polygon in &polygons {
+                let (covers_left, covers_right) = covers(polygon, piece);
+                left |= covers_left;
+                right |= covers_right;
+            }
+            let (from, to) = match (left, right) {
+                (true, false) => (vertices.index(piece.0), vertices.index(piece.1)),
+                (false, true) => (vertices.index(piece.1), vertices.index(piece.0)),
+                _ => continue,
+            };
+            if from != to && seen.insert((from, to)) {
+                boundary.push((from, to));
+            }
+        }
+    }
+
+    chain(&boundary)
+        .into_iter()
+        .map(|contour| simplified(contour.iter().map(|idx| vertices.points[*idx]).collect()))
+        .filter(|contour| contour.len() >= 3)
+        .map(|contour| contour.into_iter().map(|(x, y)| (x as f32, y as f32)).collect())
+        .collect()
+}
+
+/// Records where two edges cross or touch, as the parameter along each edge and the point to
+/// split it at. End points of an edge are never recorded for that edge.
+fn intersect(a: Edge, b: Edge, a_splits: &mut Vec<(f64, Point)>, b_splits: &mut Vec<(f64, Point)>) {
+    if a.0.0.min(a.1.0) > b.0.0.max(b.1.0) + EPSILON
+        || b.0.0.min(b.1.0) > a.0.0.max(a.1.0) + EPSILON
+        || a.0.1.min(a.1.1) > b.0.1.max(b.1.1) + EPSILON
+        || b.0.1.min(b.1.1) > a.0.1.max(a.1.1) + EPSILON
+    {
+        return;
+    }
+
+    let (length_a, length_b) = (distance(a.0, a.1), distance(b.0, b.1));
+    if on_line(b.0, a) && on_line(b.1, a) {
+        // collinear edges are split at each other's end points
+        a_splits.extend([b.0, b.1].into_iter().filter_map(|point| interior(a, point, length_a)));
+        b_splits.extend([a.0, a.1].into_iter().filter_map(|point| interior(b, point, length_b)));
+        return;
+    }
+
+    let r = (a.1.0 - a.0.0, a.1.1 - a.0.1);
+    let s = (b.1.0 - b.0.0, b.1.1 - b.0.1);
+    let denominator = cross(r, s);
+    if denominator == 0.0 {
+        return;
+    }
+    let qp = (b.0.0 - a.0.0, b.0.1 - a.0.1);
+    let t = cross(qp, s) / denominator;
+    let u = cross(qp, r) / denominator;
+    let (epsilon_t, epsilon_u) = (EPSILON / length_a, EPSILON / length_b);
+    if t < -epsilon_t || t > 1.0 + epsilon_t || u < -epsilon_u || u > 1.0 + epsilon_u {
+        return;
+    }
+
+    // snap to existing end points, so touching edges meet exactly
+    let point = if t <= epsilon_t {
+        a.0
+    } else if t >= 1.0 - epsilon_t {
+        a.1
+    } else if u <= epsilon_u {
+        b.0
+    } else if u >= 1.0 - epsilon_u {
+        b.1
+    } else {
+        (a.0.0 + t * r.0, a.0.1 + t * r.1)
+    };
+    if t > epsilon_t && t < 1.0 - epsilon_t {
+        a_splits.push((t, point));
+    }
+    if u > epsilon_u && u < 1.0 - epsilon_u {
+        b_splits.push((u, point));
+    }
+}
+
+/// The parameter of `point` along `edge`, if it lies strictly between its end points.
+fn interior(edge: Edge, point: Point, length: f64) -> Option<(f64, Point)> {
+    let r = (edge.1.0 - edge.0.0, edge.1.1 - edge.0.1);
+    let t = ((point.0 - edge.0.0) * r.0 + (point.1 - edge.0.1) * r.1) / (length * length);
+    let epsilon = EPSILON / length;
+    (t > epsilon && t < 1.0 - epsilon).then_some((t, point))
+}
+
+/// Signed distance of `point` from the line through `edge`, positive on its left.
+fn side(point: Point, edge: Edge) -> f64 {
+    let r = (edge.1.0 - edge.0.0, edge.1.1 - edge.0.1);
+    cross(r, (point.0 - edge.0.0, point.1 - edge.0.1)) / distance(edge.0, edge.1)
+}
+
+fn on_line(point: Point, edge: Edge) -> bool {
+    side(point, edge).abs() <= EPSILON
+}
+
+/// Whether `polygon` covers the area just left and just right of `piece`. Pieces never cross
+/// an edge, so they either run along one or are on the same side of all of them.
+fn covers(polygon: &[Point], piece: Edge) -> (bool, bool) {
+    let mid = ((piece.0.0 + piece.1.0) / 2.0, (piece.0.1 + piece.1.1) / 2.0);
+    let mut along = None;
+    for idx in 0..polygon.len() {
+        let edge = (polygon[idx], polygon[(idx + 1) % polygon.len()]);
+        if distance(edge.0, edge.1) <= EPSILON {
+            continue;
+        }
+        if on_line(piece.0, edge) && on_line(piece.1, edge) {
+            along = Some(edge);
+        } else if side(mid, edge) < 0.0 {
+            return (false, false);
+        }
+    }
+    match along {
+        None => (true, true),
+        // the inside of a positively oriented polygon is left of its edges
+        Some((a, b)) if (b.0 - a.0) * (piece.1.0 - piece.0.0) + (b.1 - a.1) * (piece.1.1 - piece.0.1) > 0.0 => (true, false),
+        Some(_) => (false, true),
+    }
+}
+
+/// Follows the boundary edges, given as vertex indices, around into closed contours.
+fn chain(edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, (from, _)) in edges.iter().enumerate() {
+        outgoing.entry(*from).or_default().push(idx);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut closed = Vec::new();
+    let mut open: Vec<Vec<usize>> = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        let start = edges[first].0;
+        let mut contour = vec![start];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let to = edges[current].1;
+            if to == start {
+                closed.push(contour);
+                break;
+            }
+            contour.push(to);
+            let next = outgoing
+                .get(&to)
+                .and_then(|candidates| candidates.iter().find(|idx| !used[**idx]));
+            match next {
+                Some(next) => current = *next,
+                None => {
+                    open.push(contour);
+                    break;
+                },
+            }
+        }
+    }
+
+    // a walk that started halfway along a contour ends where an earlier walk began
+    while let Some(mut contour) = open.pop() {
+        let end = contour[contour.len() - 1];
+        if let Some(idx) = open.iter().position(|other| other[0] == end) {
+            contour.pop();
+            contour.extend(open.swap_remove(idx));
+            open.push(contour);
+        } else {
+            if contour[0] == end {
+                contour.pop();
+            }
+            closed.push(contour);
+        }
+    }
+    closed
+}
+
+/// Drops points that lie on the straight line between their neighbours.
+fn simplified(mut contour: Vec<Point>) -> Vec<Point> {
+    let mut idx = 0;
+    while idx < contour.len() && contour.len() >= 3 {
+        let prev = contour[(idx + contour.len() - 1) % contour.len()];
+        let next = contour[(idx + 1) % contour.len()];
+        if distance(prev, next) > EPSILON && on_line(contour[idx], (prev, next)) {
+            contour.remove(idx);
+            idx = idx.saturating_sub(1);
+        } else {
+            idx += 1;
+        }
+    }
+    contour
+}
+
+/// Merges points closer than [`EPSILON`] into a single vertex.
+#[derive(Default)]
+struct Vertices {
+    points: Vec<Point>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Vertices {
+    fn index(&mut self, point: Point) -> usize {
+        let cell = ((point.0 / EPSILON).floor() as i64, (point.1 / EPSILON).floor() as i64);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for idx in self.grid.get(&(cell.0 + dx, cell.1 + dy)).into_iter().flatten() {
+                    let other = self.points[*idx];
+                    if (other.0 - point.0).abs() <= EPSILON && (other.1 - point.1).abs() <= EPSILON {
+                        return *idx;
+                    }
+                }
+            }
+        }
+        self.points.push(point);
+        self.grid.entry(cell).or_default().push(self.points.len() - 1);
+        self.points.len() - 1
+    }
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outline::{LineCap, LineJoin, Pen};
+
+    fn rect(from: (f32, f32), to: (f32, f32)) -> Contour {
+        vec![from, (to.0, from.1), to, (from.0, to.1)]
+    }
+
+    fn areas(contours: &[Contour]) -> Vec<f32> {
+        let mut result: Vec<f32> = contours.iter().map(|contour| signed_area(contour)).collect();
+        result.sort_by(f32::total_cmp);
+        result
+    }
+
+    fn assert_areas(contours: &[Contour], expected: &[f32]) {
+        let actual = areas(contours);
+        assert_eq!(actual.len(), expected.len(), "{contours:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} instead of {expected:?}");
+        }
+    }
+
+    /// Whether `point` is inside `contours` by the nonzero rule.
+    fn winding(contours: &[Contour], point: (f32, f32)) -> i32 {
+        let mut result = 0;
+        for contour in contours {
+            for idx in 0..contour.len() {
+                let (a, b) = (contour[idx], contour[(idx + 1) % contour.len()]);
+                let side = (b.0 - a.0) * (point.1 - a.1) - (b.1 - a.1) * (point.0 - a.0);
+                if a.1 <= point.1 && b.1 > point.1 && side > 0.0 {
+                    result += 1;
+                } else if b.1 <= point.1 && a.1 > point.1 && side < 0.0 {
+                    result -= 1;
+                }
+            }
+        }
+        result
+    }
+
+    /// Compares the union with its input on a grid of sample points, skipping those right on
+    /// an edge.
+    fn assert_same_area(polygons: &[Contour], bounds: (f32, f32, f32, f32)) {
+        let result = union(polygons);
+        let steps = 200;
+        for row in 0..=steps {
+            for column in 0..=steps {
+                let point = (
+                    bounds.0 + (bounds.2 - bounds.0) * (column as f32 + 0.37) / steps as f32,
+                    bounds.1 + (bounds.3 - bounds.1) * (row as f32 + 0.61) / steps as f32,
+                );
+                let near_edge = polygons.iter().chain(&result).any(|contour| {
+                    (0..contour.len()).any(|idx| {
+                        let (a, b) = (contour[idx], contour[(idx + 1) % contour.len()]);
+                        let length = (b.0 - a.0).hypot(b.1 - a.1);
+                        let t = (((point.0 - a.0) * (b.0 - a.0) + (point.1 - a.1) * (b.1 - a.1)) / (length * length)).clamp(0.0, 1.0);
+                        (a.0 + t * (b.0 - a.0) - point.0).hypot(a.1 + t * (b.1 - a.1) - point.1) < 1e-3
+                    })
+                });
+                if near_edge {
+                    continue;
+                }
+                let covered = polygons.iter().any(|polygon| winding(std::slice::from_ref(polygon), point) != 0);
+                let inside = winding(&result, point);
+                assert!(inside == 0 || inside == 1, "winding {inside} at {point:?}");
+                assert_eq!(covered, inside == 1, "at {point:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn disjoint_polygons_stay_apart() {
+        assert_areas(&union(&[rect((0.0, 0.0), (1.0, 1.0)), rect((2.0, 0.0), (3.0, 1.0))]), &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn overlapping_squares_merge() {
+        let result = union(&[rect((0.0, 0.0), (2.0, 2.0)), rect((1.0, 1.0), (3.0, 3.0))]);
+        assert_areas(&result, &[7.0]);
+        assert_eq!(result[0].len(), 8);
+    }
+
+    #[test]
+    fn contained_polygons_disappear() {
+        let result = union(&[rect((0.0, 0.0), (3.0, 3.0)), rect((1.0, 1.0), (2.0, 2.0))]);
+        assert_areas(&result, &[9.0]);
+        assert_eq!(result[0].len(), 4);
+    }
+
+    #[test]
+    fn shared_edges_are_dropped() {
+        let result = union(&[rect((0.0, 0.0), (1.0, 1.0)), rect((1.0, 0.0), (2.0, 1.0)), rect((0.5, 0.0), (1.5, 1.0))]);
+        assert_areas(&result, &[2.0]);
+        assert_eq!(result[0].len(), 4);
+    }
+
+    #[test]
+    fn rings_keep_their_hole() {
+        let frame = [
+            rect((0.0, 0.0), (3.0, 1.0)),
+            rect((2.0, 0.0), (3.0, 3.0)),
+            rect((0.0, 2.0), (3.0, 3.0)),
+            rect((0.0, 0.0), (1.0, 3.0)),
+        ];
+        assert_areas(&union(&frame), &[-1.0, 9.0]);
+        assert_same_area(&frame, (-0.5, -0.5, 3.5, 3.5));
+    }
+
+    #[test]
+    fn degenerate_polygons_are_ignored() {
+        let sliver = vec![(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)];
+        assert!(union(std::slice::from_ref(&sliver)).is_empty());
+        assert_areas(&union(&[sliver, rect((0.0, 0.0), (1.0, 1.0))]), &[1.0]);
+    }
+
+    #[test]
+    fn strokes_meeting_at_shallow_angles() {
+        let pen = Pen { width: 0.05, cap: LineCap::Round, join: LineJoin::Round, miter_limit: 4.0 };
+        let pieces = pen.pieces(&[(0.1, 0.5), (0.5, 0.5), (0.9, 0.503), (0.5, 0.507)]);
+        assert_same_area(&pieces, (0.0, 0.4, 1.0, 0.6));
+
+        let pen = Pen { cap: LineCap::Butt, join: LineJoin::Miter, ..pen };
+        let pieces = pen.pieces(&[(0.1, 0.1), (0.9, 0.9), (0.1, 0.12)]);
+        assert_same_area(&pieces, (0.0, 0.0, 1.0, 1.0));
+    }
+}
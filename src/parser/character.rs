@@ -1,18 +1,38 @@
+use crate::lexer::Span;
 use crate::parser::SyntaxTree;
 
-#[derive(Debug)]
+/// One definition, `[§] name[, alias]* := tree [? annotation]* ;`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Character {
+    /// Whether the definition is marked with `§`.
     pub is_radical: bool,
+    /// The first name followed by the aliases.
     pub names: Vec<String>,
+    /// Where each of [`names`](Character::names) is written.
+    pub name_spans: Vec<Span>,
+    /// Everything after the `:=`, wrapped in [`SyntaxTree::Annotated`] if it has annotations.
     pub syntax_tree: SyntaxTree,
-    pub annotations: Annotations,
+    /// The whole definition, from the `§` or first name to the `;`.
+    pub span: Span,
 }
 
-type Rect = ((f32, f32), (f32, f32));
+impl Character {
+    /// Annotations attached to the whole definition, i.e. `name := ... ? ...;`.
+    pub fn annotations(&self) -> Annotations {
+        self.syntax_tree.annotations().unwrap_or_default()
+    }
+}
 
-#[derive(Debug)]
+/// Rectangle in the unit square, as its top left and bottom right corner.
+pub type Rect = ((f32, f32), (f32, f32));
+
+/// The `? ...` annotations of a definition or a parenthesized tree.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Annotations {
+    /// `? := a => b`, where the glyph is placed when emitted on its own. Only allowed on a whole
+    /// definition, not inside `( ... )`.
     pub standalone: Rect,
+    /// `? {} := a => b + c => d`, where the outer and the inner part of `outer { inner }` go.
     pub inner: (Rect, Rect)
 }
 
@@ -23,4 +43,10 @@ impl Annotations {
             inner: (((0.0, 0.0), (1.0, 1.0)), ((0.2, 0.2), (0.8, 0.8))),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Annotations::new()
+    }
+}
@@ -1,6 +1,27 @@
+//! Syntax tree of `.glyph` sources. [`Parser`] turns the tokens of a [`Lexer`] into one
+//! [`Character`] per definition, recovering at the next `;` after an error.
+
 mod syntax_tree;
 mod parser;
 pub mod character;
 
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Lexer;
+
+pub use character::{Annotations, Character};
 pub use parser::Parser;
-pub use syntax_tree::SyntaxTree;
\ No newline at end of file
+pub use syntax_tree::SyntaxTree;
+
+/// Parses every definition in `src`, returning those that parsed and the problems with the
+/// others, in source order.
+pub fn parse(src: &str) -> (Vec<Character>, Vec<Diagnostic>) {
+    let mut characters = Vec::new();
+    let mut diagnostics = Vec::new();
+    for next in Parser::new(Lexer::new(src)) {
+        match next {
+            Ok(next) => characters.push(next),
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    (characters, diagnostics)
+}
@@ -1,240 +1,394 @@
-use std::iter::Peekable;
-use crate::lexer::{Lexer, Token};
+use crate::diagnostic::{Diagnostic, ErrorKind};
+use crate::lexer::{Lexer, Span, SpannedToken, Token};
 use crate::parser::character::{Annotations, Character};
 use crate::parser::SyntaxTree;
 
+type ParseResult<T> = Result<T, Diagnostic>;
+type Rect = ((f32, f32), (f32, f32));
+
+/// Recursive descent parser, yielding one [`Character`] or [`Diagnostic`] per definition.
+/// After an error it skips to the next `;`.
 pub struct Parser<'a> {
-    lexer: Peekable<Lexer<'a>>,
+    lexer: Lexer<'a>,
+    peeked: Option<SpannedToken>,
+    /// Span of the last consumed token.
+    previous: Span,
 }
 
 macro_rules! expect {
-    ($iterator:expr, $pattern:pat, $then:expr) => {
-        //#[must_use]
-        match $iterator.next() {
-            Some($pattern) => Some($then),
-            _ => None,
+    ($parser:expr, $pattern:pat => $then:expr, $expected:expr) => {
+        match $parser.next_token() {
+            Some(SpannedToken { token: $pattern, .. }) => Ok($then),
+            found => Err($parser.unexpected($expected, found)),
         }
     };
-    ($iterator:expr, $pattern:pat) => {
-        expect!($iterator, $pattern, ())
+    ($parser:expr, $pattern:pat, $expected:expr) => {
+        expect!($parser, $pattern => (), $expected)
     };
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(lexer: Peekable<Lexer<'a>>) -> Self {
-        Parser { lexer }
+    /// Parses the tokens of `lexer`, skipping whitespace and comments if it is one
+    /// [`with_trivia`](Lexer::with_trivia).
+    pub fn new(lexer: Lexer<'a>) -> Self {
+        Parser { lexer, peeked: None, previous: Span::default() }
+    }
+
+    /// The next token of the lexer that is not trivia.
+    fn fetch(&mut self) -> Option<SpannedToken> {
+        self.lexer.find(|it| !it.token.is_trivia())
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = self.fetch();
+        }
+        self.peeked.as_ref().map(|it| &it.token)
+    }
+
+    fn peek_span(&mut self) -> Span {
+        self.peek();
+        match &self.peeked {
+            Some(next) => next.span,
+            None => self.lexer.current_span(),
+        }
     }
 
-    fn parse_arrow(&mut self) -> Option<SyntaxTree> {
-        let Some(Token::ValuePair(x, y)) = self.lexer.next() else {
-            return None;
+    fn next_token(&mut self) -> Option<SpannedToken> {
+        let next = self.peeked.take().or_else(|| self.fetch());
+        if let Some(next) = &next {
+            self.previous = next.span;
+        }
+        next
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.next_token();
+        }
+        matches
+    }
+
+    /// Builds the error for `found` and puts it back, so that nothing is consumed on failure.
+    fn unexpected(&mut self, expected: &'static str, found: Option<SpannedToken>) -> Diagnostic {
+        let diagnostic = match &found {
+            Some(SpannedToken { token: Token::Error(error), span }) => Diagnostic::new(ErrorKind::Lex(error.clone()), *span),
+            Some(SpannedToken { token, span }) => Diagnostic::new(ErrorKind::UnexpectedToken { expected, found: Some(token.clone()) }, *span),
+            None => Diagnostic::new(ErrorKind::UnexpectedToken { expected, found: None }, self.lexer.current_span()),
         };
-        match self.lexer.peek() {
-            Some(Token::Arrow) => self.parse_line((x, y)),
-            Some(Token::TildeArrow) => self.parse_line((x, y)),
-            Some(Token::WideArrow) => self.parse_bound((x, y)),
-            _ => None,
+        self.peeked = found;
+        diagnostic
+    }
+
+    /// Skips everything up to and including the next `;`, so parsing can resume at the next character.
+    fn synchronize(&mut self) {
+        while let Some(SpannedToken { token, .. }) = self.next_token() {
+            if token == Token::Semicolon {
+                break;
+            }
+        }
+    }
+
+    fn parse_arrow(&mut self) -> ParseResult<SyntaxTree> {
+        let span = self.peek_span();
+        let start = expect!(self, Token::ValuePair(x, y) => (x, y), "coordinates, identifier or `(`")?;
+        match self.peek() {
+            Some(Token::Arrow) => self.parse_line(start, span),
+            Some(Token::TildeArrow) => self.parse_line(start, span),
+            Some(Token::WideArrow) => self.parse_bound(start, span),
+            _ => {
+                let found = self.next_token();
+                Err(self.unexpected("`->`, `~>` or `=>`", found))
+            },
         }
     }
 
-    fn parse_line(&mut self, start: (f32, f32)) -> Option<SyntaxTree> {
+    fn parse_line(&mut self, start: (f32, f32), span: Span) -> ParseResult<SyntaxTree> {
         let mut lines = vec![vec![start]];
         loop {
-            match self.lexer.peek()? {
-                Token::Arrow => {
-                    let _ = self.lexer.next();
-                    let Some(Token::ValuePair(x, y)) = self.lexer.next() else { return None; };
+            match self.peek() {
+                Some(Token::Arrow) => {
+                    let _ = self.next_token();
+                    let (x, y) = expect!(self, Token::ValuePair(x, y) => (x, y), "coordinates")?;
                     lines.last_mut().unwrap().push((x, y));
                     lines.push(vec![(x, y)]);
                 },
-                Token::TildeArrow => {
-                    let _ = self.lexer.next();
-                    let Some(Token::ValuePair(x, y)) = self.lexer.next() else { return None; };
+                Some(Token::TildeArrow) => {
+                    let _ = self.next_token();
+                    let (x, y) = expect!(self, Token::ValuePair(x, y) => (x, y), "coordinates")?;
                     lines.last_mut().unwrap().push((x, y));
                 },
                 _ => break,
             }
         }
         let _ = lines.pop();
-        Some(SyntaxTree::Lines(lines))
+        Ok(SyntaxTree::Lines(lines, span.to(self.previous)))
     }
 
-    fn parse_bound(&mut self, start: (f32, f32)) -> Option<SyntaxTree> {
-        let Some(Token::WideArrow) = self.lexer.next() else { return None; };
-        let Some(Token::ValuePair(x, y)) = self.lexer.next() else { return None; };
-        Some(SyntaxTree::Bounds(start, (x, y)))
+    fn parse_bound(&mut self, start: (f32, f32), span: Span) -> ParseResult<SyntaxTree> {
+        let (start, end) = self.parse_rect(start)?;
+        Ok(SyntaxTree::Bounds(start, end, span.to(self.previous)))
     }
 
-    fn parse_value(&mut self) -> Option<SyntaxTree> {
-        match self.lexer.peek()? {
-            Token::LParen => {
-                let _ = self.lexer.next()?;
-                let tree = self.parse_tree()?;
-                let Token::RParen = self.lexer.next()? else { return None; };
-                Some(tree)
+    fn parse_rect(&mut self, start: (f32, f32)) -> ParseResult<Rect> {
+        expect!(self, Token::WideArrow, "`=>`")?;
+        let end = expect!(self, Token::ValuePair(x, y) => (x, y), "coordinates")?;
+        Ok((start, end))
+    }
+
+    fn parse_value(&mut self) -> ParseResult<SyntaxTree> {
+        let start = self.peek_span();
+        match self.peek() {
+            Some(Token::LParen) => {
+                let _ = self.next_token();
+                let tree = self.parse_annotated_tree(false)?;
+                expect!(self, Token::RParen, "`)`")?;
+                Ok(tree)
             },
-            Token::LBracket => {
-                let _ = self.lexer.next()?;
+            Some(Token::LBracket) => {
+                let _ = self.next_token();
                 let tree = self.parse_tree()?;
-                let Token::RBracket = self.lexer.next()? else { return None; };
-                Some(SyntaxTree::HalfwaySquare(Box::new(tree)))
+                expect!(self, Token::RBracket, "`]`")?;
+                Ok(SyntaxTree::HalfwaySquare(Box::new(tree), start.to(self.previous)))
             },
-            Token::LWideBracket => {
-                let _ = self.lexer.next()?;
+            Some(Token::LWideBracket) => {
+                let _ = self.next_token();
                 let tree = self.parse_tree()?;
-                let Token::RWideBracket = self.lexer.next()? else { return None; };
-                Some(SyntaxTree::Square(Box::new(tree)))
+                expect!(self, Token::RWideBracket, "`]]`")?;
+                Ok(SyntaxTree::Square(Box::new(tree), start.to(self.previous)))
             },
-            Token::Ident(_) => {
-                let Some(Token::Ident(ident)) = self.lexer.next() else { return None; };
-                Some(SyntaxTree::Ident(ident))
+            Some(Token::Ident(_)) => {
+                let ident = expect!(self, Token::Ident(ident) => ident, "identifier")?;
+                Ok(SyntaxTree::Ident(ident, start))
             }
             _ => self.parse_arrow()
         }
     }
 
-    fn parse_inner(&mut self) -> Option<SyntaxTree> {
+    fn parse_inner(&mut self) -> ParseResult<SyntaxTree> {
+        let start = self.peek_span();
         let value = self.parse_value()?;
-        if self.lexer.next_if_eq(&Token::LCurly).is_some() {
+        if self.eat(&Token::LCurly) {
             let inner = self.parse_tree()?;
-            match self.lexer.next() {
-                Some(Token::RCurly) => Some(SyntaxTree::Inner(Box::new(value), Box::new(inner))),
-                _ => None,
-            }
+            expect!(self, Token::RCurly, "`}`")?;
+            Ok(SyntaxTree::Inner(Box::new(value), Box::new(inner), start.to(self.previous)))
         } else {
-            Some(value)
+            Ok(value)
         }
     }
 
-    fn parse_shrink(&mut self) -> Option<SyntaxTree> {
+    fn parse_shrink(&mut self) -> ParseResult<SyntaxTree> {
+        let start = self.peek_span();
         let mut shrinkage = 0;
-        while self.lexer.next_if_eq(&Token::Ampersand).is_some() {
+        while self.eat(&Token::Ampersand) {
             shrinkage += 1;
         }
-        Some(SyntaxTree::Shrink(shrinkage, Box::new(self.parse_inner()?)))
+        let tree = self.parse_inner()?;
+        Ok(SyntaxTree::Shrink(shrinkage, Box::new(tree), start.to(self.previous)))
     }
 
-    fn parse_vertical(&mut self) -> Option<SyntaxTree> {
+    fn parse_vertical(&mut self) -> ParseResult<SyntaxTree> {
         let mut result = vec![self.parse_shrink()?];
-        while self.lexer.next_if_eq(&Token::Slash).is_some() {
+        while self.eat(&Token::Slash) {
             result.push(self.parse_shrink()?);
         }
         if result.len() == 1 {
-            Some(result.pop()?)
+            Ok(result.pop().unwrap())
         } else {
-            Some(SyntaxTree::Vertical(result))
+            let span = result[0].span().to(result[result.len() - 1].span());
+            Ok(SyntaxTree::Vertical(result, span))
         }
     }
 
-    fn parse_horizontal(&mut self) -> Option<SyntaxTree> {
+    fn parse_horizontal(&mut self) -> ParseResult<SyntaxTree> {
         let mut result = vec![self.parse_vertical()?];
-        while self.lexer.next_if_eq(&Token::Pipe).is_some() {
+        while self.eat(&Token::Pipe) {
             result.push(self.parse_vertical()?);
         }
         if result.len() == 1 {
-            Some(result.pop()?)
+            Ok(result.pop().unwrap())
         } else {
-            Some(SyntaxTree::Horizontal(result))
+            let span = result[0].span().to(result[result.len() - 1].span());
+            Ok(SyntaxTree::Horizontal(result, span))
         }
     }
 
-    fn parse_tree(&mut self) -> Option<SyntaxTree> {
+    fn parse_tree(&mut self) -> ParseResult<SyntaxTree> {
         let mut result = vec![self.parse_horizontal()?];
-        while self.lexer.next_if_eq(&Token::Plus).is_some() {
+        while self.eat(&Token::Plus) {
             result.push(self.parse_horizontal()?);
         }
         if result.len() == 1 {
-            Some(result.pop()?)
+            Ok(result.pop().unwrap())
         } else {
-            Some(SyntaxTree::Plus(result))
+            let span = result[0].span().to(result[result.len() - 1].span());
+            Ok(SyntaxTree::Plus(result, span))
         }
     }
 
+    /// `definition` is false inside `( ... )`, where standalone bounds would have no effect.
+    fn parse_annotated_tree(&mut self, definition: bool) -> ParseResult<SyntaxTree> {
+        let start = self.peek_span();
+        let tree = self.parse_tree()?;
+        if self.peek() != Some(&Token::QuestionMark) {
+            return Ok(tree);
+        }
+        let annotations = self.parse_annotations(definition)?;
+        Ok(SyntaxTree::Annotated(Box::new(tree), annotations, start.to(self.previous)))
+    }
 
-    fn parse_bounds_as_rect_pair(&mut self) -> Option<((f32, f32), (f32, f32))> {
-        let Some(Token::ValuePair(x, y)) = self.lexer.next() else {
-            return None;
-        };
-
-        let Some(SyntaxTree::Bounds(start, end)) = self.parse_bound((x, y)) else {
-            return None;
-        };
-
-        Some((start, end))
+    fn parse_bounds_as_rect_pair(&mut self) -> ParseResult<Rect> {
+        let start = expect!(self, Token::ValuePair(x, y) => (x, y), "coordinates")?;
+        self.parse_rect(start)
     }
 
-    fn parse_annotation(&mut self, annotations: &mut Annotations) -> Option<()> {
+    fn parse_annotation(&mut self, annotations: &mut Annotations, definition: bool) -> ParseResult<()> {
         // := standalone
         // {} := outer + inner;
-        match self.lexer.next()? {
-            Token::LCurly => {
-                let Some(Token::RCurly) = self.lexer.next() else {
-                    return None;
-                };
-
-                let Some(Token::Assign) = self.lexer.next() else {
-                    return None;
-                };
+        match self.next_token() {
+            Some(SpannedToken { token: Token::LCurly, .. }) => {
+                expect!(self, Token::RCurly, "`}`")?;
+                expect!(self, Token::Assign, "`:=`")?;
 
                 let start = self.parse_bounds_as_rect_pair()?;
 
-                let Some(Token::Plus) = self.lexer.next() else {
-                    return None;
-                };
+                expect!(self, Token::Plus, "`+`")?;
 
                 let end = self.parse_bounds_as_rect_pair()?;
 
                 annotations.inner = (start, end);
 
-                Some(())
+                Ok(())
             },
-            Token::Assign => {
+            Some(SpannedToken { token: Token::Assign, span }) => {
                 let rect = self.parse_bounds_as_rect_pair()?;
+                if !definition {
+                    return Err(Diagnostic::new(ErrorKind::NestedStandaloneBounds, span.to(self.previous)));
+                }
 
                 annotations.standalone = rect;
 
-                Some(())
+                Ok(())
             },
-            _ => None,
+            found => Err(self.unexpected("`{}` or `:=`", found)),
         }
     }
 
-    fn parse_annotations(&mut self) -> Option<Annotations> {
+    fn parse_annotations(&mut self, definition: bool) -> ParseResult<Annotations> {
         let mut result = Annotations::new();
-        while self.lexer.next_if_eq(&Token::QuestionMark).is_some() {
-            self.parse_annotation(&mut result)?;
+        while self.eat(&Token::QuestionMark) {
+            self.parse_annotation(&mut result, definition)?;
         }
-        Some(result)
+        Ok(result)
     }
-}
-
-
-impl Iterator for Parser<'_> {
-    type Item = Character;
 
-    fn next(&mut self) -> Option<Character> {
+    fn parse_character(&mut self) -> ParseResult<Character> {
         //[§|E] name[, name]* := value [? [op|E] := value]* ;
-        let is_radical = self.lexer.next_if(|it| matches!(it, Token::Section)).is_some();
-        let mut names = vec![expect!(self.lexer, Token::Ident(n), n)?];
-        loop {
-            match self.lexer.next() {
-                Some(Token::Comma) => {},
-                Some(Token::Assign) => break,
-                _ => return None,
-            }
-            names.push(expect!(self.lexer, Token::Ident(n), n)?);
+        let start = self.peek_span();
+        let is_radical = self.eat(&Token::Section);
+        let mut name_spans = vec![self.peek_span()];
+        let mut names = vec![expect!(self, Token::Ident(n) => n, "character name")?];
+        while self.eat(&Token::Comma) {
+            name_spans.push(self.peek_span());
+            names.push(expect!(self, Token::Ident(n) => n, "character name")?);
         }
-        let syntax_tree = self.parse_tree()?;
+        expect!(self, Token::Assign, "`,` or `:=`")?;
 
-        let annotations = self.parse_annotations()?;
+        let syntax_tree = self.parse_annotated_tree(true)?;
 
-        let Some(Token::Semicolon) = self.lexer.next() else { return None; };
+        let end = self.peek_span();
+        expect!(self, Token::Semicolon, "`;`")?;
 
-        Some(Character {
+        Ok(Character {
             is_radical,
             names,
+            name_spans,
             syntax_tree,
-            annotations,
+            span: start.to(end),
         })
     }
-}
\ No newline at end of file
+}
+
+
+impl Iterator for Parser<'_> {
+    type Item = ParseResult<Character>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.peek()?;
+        let result = self.parse_character();
+        if result.is_err() {
+            self.synchronize();
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::ErrorKind;
+    use crate::lexer::{Lexer, Token};
+    use crate::parser::{parse, Parser};
+
+    fn names(src: &str) -> Vec<String> {
+        parse(src).0.into_iter().map(|character| character.names[0].clone()).collect()
+    }
+
+    #[test]
+    fn resumes_after_the_next_semicolon() {
+        let src = "a := 0 0 -> ;\nb := 0 0 -> 1 1;\nc := ) ;\nd := b;";
+        assert_eq!(names(src), ["b", "d"]);
+        let (_, diagnostics) = parse(src);
+        let lines: Vec<usize> = diagnostics.iter().map(|diagnostic| diagnostic.line()).collect();
+        assert_eq!(lines, [1, 3]);
+        assert_eq!(
+            diagnostics[0].kind,
+            ErrorKind::UnexpectedToken { expected: "coordinates", found: Some(Token::Semicolon) },
+        );
+    }
+
+    #[test]
+    fn missing_semicolon_swallows_the_next_definition() {
+        let (characters, diagnostics) = parse("a := 0 0 -> 1 1\nb := a;\nc := 0 0 -> 1 0;");
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].names, ["c"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line(), diagnostics[0].column()), (2, 1));
+    }
+
+    #[test]
+    fn errors_at_the_end_of_input() {
+        let (characters, diagnostics) = parse("a := 0 0 -> 1 1;\nb := a");
+        assert_eq!(characters.len(), 1);
+        assert_eq!(diagnostics[0].kind, ErrorKind::UnexpectedToken { expected: "`;`", found: None });
+        assert_eq!((diagnostics[0].line(), diagnostics[0].column()), (2, 7));
+    }
+
+    #[test]
+    fn lex_errors_are_reported_once() {
+        let (characters, diagnostics) = parse("a := 0 0 -> 1 !;\nb := 0 0 -> 1 1;");
+        assert_eq!(characters.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].kind, ErrorKind::Lex(_)));
+    }
+
+    #[test]
+    fn standalone_bounds_only_on_whole_definitions() {
+        let src = "a := 0 0 -> 1 1 ? := 0 0 => 0.5 1;\nb := (a ? := 0 0 => 0.5 1) | a;\nc := (a ? {} := 0 0 => 1 1 + 0 0 => 1 1) { a };";
+        let (characters, diagnostics) = parse(src);
+        assert_eq!(names(src), ["a", "c"]);
+        assert_eq!(characters[0].annotations().standalone, ((0.0, 0.0), (0.5, 1.0)));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, ErrorKind::NestedStandaloneBounds);
+        assert_eq!(diagnostics[0].span.text(src), ":= 0 0 => 0.5 1");
+    }
+
+    #[test]
+    fn trivia_is_skipped() {
+        let src = "# comment\na , b := 0 0 -> 1 1 ; # trailing\nc := [ a ] # inside\n | b;\nd := 0 0 ->  ;  ";
+        let with_trivia: Vec<_> = Parser::new(Lexer::with_trivia(src)).collect();
+        let without: Vec<_> = Parser::new(Lexer::new(src)).collect();
+        assert_eq!(with_trivia, without);
+        assert_eq!(with_trivia.iter().filter(|it| it.is_ok()).count(), 2);
+    }
+}
@@ -1,14 +1,84 @@
-// TODO: add Annotated(...) for ? ... instead of storing annotations separately -> also allows (... ? {} := ...) { ... } being inlined
-#[derive(Debug)]
+use crate::lexer::Span;
+use crate::parser::character::Annotations;
+
+/// The right-hand side of a definition. Every node carries its span, see [`SyntaxTree::span`].
+#[derive(Debug, Clone, PartialEq)]
 pub enum SyntaxTree {
-    Ident(String),
-    Lines(Vec<Vec<(f32, f32)>>),
-    Bounds((f32, f32), (f32, f32)),
-    Plus(Vec<SyntaxTree>),
-    Horizontal(Vec<SyntaxTree>),
-    Vertical(Vec<SyntaxTree>),
-    Inner(Box<SyntaxTree>, Box<SyntaxTree>),
-    Shrink(usize, Box<SyntaxTree>),
-    Square(Box<SyntaxTree>),
-    HalfwaySquare(Box<SyntaxTree>),
+    /// Reference to another character by name or alias.
+    Ident(String, Span),
+    /// `a -> b ~> c -> d`, as the control points of each curve: `->` starts the next curve,
+    /// `~>` adds a control point to the current one.
+    Lines(Vec<Vec<(f32, f32)>>, Span),
+    /// `a => b`, an empty rectangle, used as the outer part of `outer { inner }`.
+    Bounds((f32, f32), (f32, f32), Span),
+    /// `a + b`, drawn on top of each other.
+    Plus(Vec<SyntaxTree>, Span),
+    /// `a | b`, side by side.
+    Horizontal(Vec<SyntaxTree>, Span),
+    /// `a / b`, stacked from top to bottom.
+    Vertical(Vec<SyntaxTree>, Span),
+    /// `outer { inner }`.
+    Inner(Box<SyntaxTree>, Box<SyntaxTree>, Span),
+    /// A tree prefixed with this many `&`, which shrink its share in `|` and `/`. Every operand
+    /// of those is wrapped, usually with zero.
+    Shrink(usize, Box<SyntaxTree>, Span),
+    /// `[[ tree ]]`, squared to the full height or width.
+    Square(Box<SyntaxTree>, Span),
+    /// `[ tree ]`, squared halfway.
+    HalfwaySquare(Box<SyntaxTree>, Span),
+    /// `( tree ? ... )` or `name := tree ? ...;`.
+    Annotated(Box<SyntaxTree>, Annotations, Span),
+}
+
+impl SyntaxTree {
+    /// Where the node is written, including its square and curly brackets. Parentheses only
+    /// group, they are not part of any node.
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxTree::Ident(_, span)
+            | SyntaxTree::Lines(_, span)
+            | SyntaxTree::Bounds(_, _, span)
+            | SyntaxTree::Plus(_, span)
+            | SyntaxTree::Horizontal(_, span)
+            | SyntaxTree::Vertical(_, span)
+            | SyntaxTree::Inner(_, _, span)
+            | SyntaxTree::Shrink(_, _, span)
+            | SyntaxTree::Square(_, span)
+            | SyntaxTree::HalfwaySquare(_, span)
+            | SyntaxTree::Annotated(_, _, span) => *span,
+        }
+    }
+
+    /// Every character name referenced in this tree, in source order.
+    pub fn references(&self) -> Vec<(&str, Span)> {
+        let mut result = Vec::new();
+        self.collect_references(&mut result);
+        result
+    }
+
+    fn collect_references<'a>(&'a self, result: &mut Vec<(&'a str, Span)>) {
+        match self {
+            SyntaxTree::Ident(ident, span) => result.push((ident, *span)),
+            SyntaxTree::Lines(_, _) | SyntaxTree::Bounds(_, _, _) => {},
+            SyntaxTree::Plus(trees, _) | SyntaxTree::Horizontal(trees, _) | SyntaxTree::Vertical(trees, _) => {
+                trees.iter().for_each(|tree| tree.collect_references(result));
+            },
+            SyntaxTree::Inner(outer, inner, _) => {
+                outer.collect_references(result);
+                inner.collect_references(result);
+            },
+            SyntaxTree::Shrink(_, tree, _) | SyntaxTree::Square(tree, _) | SyntaxTree::HalfwaySquare(tree, _) | SyntaxTree::Annotated(tree, _, _) => {
+                tree.collect_references(result);
+            },
+        }
+    }
+
+    /// Annotations of an `Annotated` tree, looking through the shrink wrapper around `( ... )`.
+    pub fn annotations(&self) -> Option<Annotations> {
+        match self {
+            SyntaxTree::Annotated(_, annotations, _) => Some(*annotations),
+            SyntaxTree::Shrink(_, tree, _) => tree.annotations(),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,142 @@
+use crate::outline::Contour;
+
+/// How much of every pixel, row by row, the `contours` (given in pixels) cover with the nonzero
+/// rule. Each edge adds the signed area between itself and the right side of the pixmap to the
+/// cells it crosses, summing a row from the left then gives the winding for each pixel, with
+/// partial coverage where an edge runs through it.
+pub(super) fn coverage(width: usize, height: usize, contours: &[Contour]) -> Vec<f32> {
+    // two spare columns take what lands on or right of the right edge
+    let stride = width + 2;
+    let mut accumulation = vec![0.0; stride * height];
+    for contour in contours {
+        for idx in 0..contour.len() {
+            let to = contour[(idx + 1) % contour.len()];
+            edge(&mut accumulation, stride, width, height, contour[idx], to);
+        }
+    }
+
+    let mut result = Vec::with_capacity(width * height);
+    for row in accumulation.chunks(stride) {
+        let mut winding = 0.0f32;
+        for cell in &row[..width] {
+            winding += cell;
+            result.push(winding.abs().min(1.0));
+        }
+    }
+    result
+}
+
+fn edge(accumulation: &mut [f32], stride: usize, width: usize, height: usize, from: (f32, f32), to: (f32, f32)) {
+    if from.1 == to.1 {
+        return;
+    }
+    let (direction, top, bottom) = if from.1 < to.1 { (1.0, from, to) } else { (-1.0, to, from) };
+    let dxdy = (bottom.0 - top.0) / (bottom.1 - top.1);
+    let start = top.1.max(0.0);
+    let end = bottom.1.min(height as f32);
+    if start >= end {
+        return;
+    }
+
+    // whatever is left of the pixmap still counts for the whole row, so x is clamped rather than clipped
+    let clamp = |x: f32| x.clamp(0.0, width as f32);
+    let mut x = top.0 + (start - top.1) * dxdy;
+    for y in start as usize..end.ceil() as usize {
+        let dy = ((y + 1) as f32).min(bottom.1) - (y as f32).max(top.1);
+        let next = x + dxdy * dy;
+        let d = dy * direction;
+        let row = &mut accumulation[y * stride..(y + 1) * stride];
+        let (x0, x1) = if x < next { (clamp(x), clamp(next)) } else { (clamp(next), clamp(x)) };
+        let (x0_floor, x1_ceil) = (x0.floor(), x1.ceil());
+        let (x0_idx, x1_idx) = (x0_floor as usize, x1_ceil as usize);
+
+        if x1_idx <= x0_idx + 1 {
+            // within a single pixel, split by the average x
+            let covered = 0.5 * (x0 + x1) - x0_floor;
+            row[x0_idx] += d * (1.0 - covered);
+            row[x0_idx + 1] += d * covered;
+        } else {
+            // across several pixels, the area grows linearly in between the two partial ends
+            let slope = (x1 - x0).recip();
+            let x0_fract = x0 - x0_floor;
+            let first = 0.5 * slope * (1.0 - x0_fract) * (1.0 - x0_fract);
+            let x1_fract = x1 - x1_ceil + 1.0;
+            let last = 0.5 * slope * x1_fract * x1_fract;
+            row[x0_idx] += d * first;
+            if x1_idx == x0_idx + 2 {
+                row[x0_idx + 1] += d * (1.0 - first - last);
+            } else {
+                let second = slope * (1.5 - x0_fract);
+                row[x0_idx + 1] += d * (second - first);
+                for cell in &mut row[x0_idx + 2..x1_idx - 1] {
+                    *cell += d * slope;
+                }
+                let before_last = second + (x1_idx - x0_idx - 3) as f32 * slope;
+                row[x1_idx - 1] += d * (1.0 - before_last - last);
+            }
+            row[x1_idx] += d * last;
+        }
+        x = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(from: (f32, f32), to: (f32, f32)) -> Contour {
+        vec![from, (to.0, from.1), to, (from.0, to.1)]
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (idx, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((a - e).abs() < 1e-5, "pixel {idx}: {a} instead of {e}");
+        }
+    }
+
+    #[test]
+    fn square_on_pixel_boundaries() {
+        #[rustfmt::skip]
+        let expected = [
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 1.0, 0.0,
+            0.0, 1.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 0.0,
+        ];
+        assert_close(&coverage(4, 4, &[rect((1.0, 1.0), (3.0, 3.0))]), &expected);
+    }
+
+    #[test]
+    fn square_between_pixels() {
+        #[rustfmt::skip]
+        let expected = [
+            0.25, 0.5, 0.25,
+            0.5,  1.0, 0.5,
+            0.25, 0.5, 0.25,
+        ];
+        assert_close(&coverage(3, 3, &[rect((0.5, 0.5), (2.5, 2.5))]), &expected);
+    }
+
+    #[test]
+    fn orientation_and_overlap_dont_matter() {
+        let mut reversed = rect((0.0, 0.0), (2.0, 1.0));
+        reversed.reverse();
+        let contours = [rect((0.0, 0.0), (2.0, 1.0)), rect((0.0, 0.0), (1.0, 1.0))];
+        assert_close(&coverage(2, 1, &[reversed]), &[1.0, 1.0]);
+        assert_close(&coverage(2, 1, &contours), &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn shapes_reaching_outside_are_clipped() {
+        assert_close(&coverage(3, 1, &[rect((-2.0, -1.0), (1.5, 3.0))]), &[1.0, 0.5, 0.0]);
+        assert_close(&coverage(2, 1, &[rect((1.5, 0.0), (5.0, 1.0))]), &[0.0, 0.5]);
+    }
+
+    #[test]
+    fn diagonal_edge() {
+        // a triangle covering the lower left half of a 2x2 pixmap
+        let triangle = vec![(0.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        assert_close(&coverage(2, 2, &[triangle]), &[0.5, 0.0, 1.0, 0.5]);
+    }
+}
@@ -0,0 +1,179 @@
+mod coverage;
+mod font;
+
+use std::fmt;
+use std::str::FromStr;
+use crate::compiler::Path;
+use crate::outline::{self, Contour, Pen};
+
+/// How far, in pixels, the outline may stray from the curves it follows.
+const TOLERANCE: f32 = 0.2;
+
+/// Straight RGBA colour, i.e. not premultiplied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+    pub const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+    pub const TRANSPARENT: Color = Color { r: 0, g: 0, b: 0, a: 0 };
+}
+
+/// Parses `#rgb`, `#rrggbb`, `#rrggbbaa` or `transparent`.
+impl FromStr for Color {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "transparent" {
+            return Ok(Color::TRANSPARENT);
+        }
+        let hex = s.strip_prefix('#').ok_or(())?;
+        if !hex.is_ascii() {
+            return Err(());
+        }
+        let channel = |idx: usize, len: usize| {
+            let value = u8::from_str_radix(&hex[idx * len..(idx + 1) * len], 16).map_err(|_| ())?;
+            Ok(if len == 1 { value * 17 } else { value })
+        };
+        match hex.len() {
+            3 => Ok(Color { r: channel(0, 1)?, g: channel(1, 1)?, b: channel(2, 1)?, a: 255 }),
+            6 => Ok(Color { r: channel(0, 2)?, g: channel(1, 2)?, b: channel(2, 2)?, a: 255 }),
+            8 => Ok(Color { r: channel(0, 2)?, g: channel(1, 2)?, b: channel(2, 2)?, a: channel(3, 2)? }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// `#rrggbb`, or `#rrggbbaa` unless opaque.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// RGBA image with 8 bits per channel, row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixmap {
+    pub width: usize,
+    pub height: usize,
+    /// `width * height` pixels of four bytes each, in straight RGBA.
+    pub data: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        let data = [background.r, background.g, background.b, background.a].repeat(width * height);
+        Pixmap { width, height, data }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let idx = (y * self.width + x) * 4;
+        Color { r: self.data[idx], g: self.data[idx + 1], b: self.data[idx + 2], a: self.data[idx + 3] }
+    }
+
+    /// Fills `contours`, given in pixels, with the nonzero rule. Edges are anti-aliased by the
+    /// share of each pixel they cover.
+    pub fn fill(&mut self, contours: &[Contour], color: Color) {
+        let coverage = coverage::coverage(self.width, self.height, contours);
+        for (pixel, coverage) in self.data.chunks_exact_mut(4).zip(coverage) {
+            if coverage > 0.0 {
+                blend(pixel, color, coverage);
+            }
+        }
+    }
+
+    /// Strokes `paths` with `pen` and fills the result, with the glyph square scaled to `size`
+    /// pixels and its top left corner at `origin`.
+    pub fn draw(&mut self, paths: &[Path], pen: &Pen, origin: (f32, f32), size: f32, color: Color) {
+        let contours: Vec<Contour> = outline::stroke(paths, pen, TOLERANCE / size)
+            .into_iter()
+            .map(|contour| contour.into_iter().map(|(x, y)| (origin.0 + x * size, origin.1 + y * size)).collect())
+            .collect();
+        self.fill(&contours, color);
+    }
+
+    /// Writes `text` in the built-in 5x7 pixel font, each font pixel drawn as `scale` by `scale`
+    /// pixels, with its top left corner at `origin`. Characters other than printable ASCII, `§`
+    /// and `…` show up as boxes.
+    pub fn text(&mut self, text: &str, origin: (usize, usize), scale: usize, color: Color) {
+        for (idx, c) in text.chars().enumerate() {
+            let left = origin.0 + idx * (font::WIDTH + 1) * scale;
+            for (column, bits) in font::columns(c).into_iter().enumerate() {
+                for row in (0..font::HEIGHT).filter(|row| (bits >> row) & 1 == 1) {
+                    self.fill_rect((left + column * scale, origin.1 + row * scale), (scale, scale), color);
+                }
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, origin: (usize, usize), size: (usize, usize), color: Color) {
+        for y in origin.1..(origin.1 + size.1).min(self.height) {
+            for x in origin.0..(origin.0 + size.0).min(self.width) {
+                let idx = (y * self.width + x) * 4;
+                blend(&mut self.data[idx..idx + 4], color, 1.0);
+            }
+        }
+    }
+}
+
+/// Size of `text` written with [`Pixmap::text`] at `scale`, as width and height in pixels.
+pub fn text_size(text: &str, scale: usize) -> (usize, usize) {
+    let count = text.chars().count();
+    ((count * (font::WIDTH + 1)).saturating_sub(1) * scale, font::HEIGHT * scale)
+}
+
+/// Puts `color` over `pixel`, with its alpha scaled by `coverage`.
+fn blend(pixel: &mut [u8], color: Color, coverage: f32) {
+    let alpha = color.a as f32 / 255.0 * coverage;
+    let below = pixel[3] as f32 / 255.0 * (1.0 - alpha);
+    let total = alpha + below;
+    if total <= 0.0 {
+        return;
+    }
+    for (channel, value) in pixel[..3].iter_mut().zip([color.r, color.g, color.b]) {
+        *channel = ((value as f32 * alpha + *channel as f32 * below) / total).round() as u8;
+    }
+    pixel[3] = (total * 255.0).round() as u8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_an_axis_aligned_square() {
+        let mut pixmap = Pixmap::new(3, 3, Color::WHITE);
+        pixmap.fill(&[vec![(0.5, 0.5), (2.5, 0.5), (2.5, 2.5), (0.5, 2.5)]], Color::BLACK);
+        let grey: Vec<u8> = (0..9).map(|idx| pixmap.pixel(idx % 3, idx / 3)).map(|color| color.r).collect();
+        assert_eq!(grey, [191, 128, 191, 128, 0, 128, 191, 128, 191]);
+        assert!((0..9).all(|idx| pixmap.pixel(idx % 3, idx / 3).a == 255));
+    }
+
+    #[test]
+    fn blends_onto_transparent_background() {
+        let mut pixmap = Pixmap::new(2, 1, Color::TRANSPARENT);
+        pixmap.fill(&[vec![(0.0, 0.0), (1.5, 0.0), (1.5, 1.0), (0.0, 1.0)]], Color { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(pixmap.pixel(0, 0), Color { r: 255, g: 0, b: 0, a: 255 });
+        assert_eq!(pixmap.pixel(1, 0), Color { r: 255, g: 0, b: 0, a: 128 });
+    }
+
+    #[test]
+    fn parses_and_prints_colors() {
+        assert_eq!("#f80".parse(), Ok(Color { r: 255, g: 136, b: 0, a: 255 }));
+        assert_eq!("#12345678".parse(), Ok(Color { r: 0x12, g: 0x34, b: 0x56, a: 0x78 }));
+        assert_eq!("transparent".parse(), Ok(Color::TRANSPARENT));
+        assert_eq!("#12345".parse::<Color>(), Err(()));
+        assert_eq!("red".parse::<Color>(), Err(()));
+        assert_eq!(Color::WHITE.to_string(), "#ffffff");
+        assert_eq!(Color { r: 1, g: 2, b: 3, a: 4 }.to_string(), "#01020304");
+    }
+}